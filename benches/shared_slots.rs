@@ -0,0 +1,117 @@
+use std::{
+    sync::Barrier,
+    time::{Duration, Instant},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// The free list as it was before reservations went lock-free: a mutex around the head index, with each slot's link
+/// stored under the slot's own lock.
+mod locked {
+    use parking_lot::{Mutex, MutexGuard};
+
+    enum Slot {
+        Occupied(u64),
+        Vacant { next: usize },
+    }
+
+    pub struct LockedSlots {
+        slots: Vec<Mutex<Slot>>,
+        next_free: Mutex<usize>,
+    }
+
+    pub struct Reserved<'a> {
+        slots: &'a LockedSlots,
+        slot: MutexGuard<'a, Slot>,
+        key: usize,
+    }
+
+    impl Drop for Reserved<'_> {
+        fn drop(&mut self) {
+            let mut next_free = MutexGuard::unlocked(&mut self.slot, || self.slots.next_free.lock());
+            *self.slot = Slot::Vacant { next: std::mem::replace(&mut *next_free, self.key) };
+        }
+    }
+
+    impl Reserved<'_> {
+        pub fn insert(&mut self, item: u64) {
+            *self.slot = Slot::Occupied(item);
+        }
+
+        /// Empties the slot and puts it back on the free list
+        pub fn take(self) -> Option<u64> {
+            match *self.slot {
+                Slot::Occupied(item) => Some(item),
+                Slot::Vacant { .. } => None,
+            }
+        }
+    }
+
+    impl LockedSlots {
+        pub fn new(capacity: usize) -> Self {
+            Self {
+                slots: (1..=capacity).map(|next| Mutex::new(Slot::Vacant { next })).collect(),
+                next_free: Mutex::new(0),
+            }
+        }
+
+        pub fn reserve(&self) -> Option<Reserved<'_>> {
+            let mut next_free = self.next_free.lock();
+            let key = *next_free;
+            let slot = self.slots.get(key)?.lock();
+            *next_free = match &*slot {
+                Slot::Vacant { next } => *next,
+                Slot::Occupied(_) => unreachable!(),
+            };
+            Some(Reserved { slots: self, slot, key })
+        }
+    }
+}
+
+/// Runs `iters` reserve/release cycles on each of `threads` threads at once, returning the wall time
+fn contended(threads: usize, iters: u64, cycle: impl Fn() + Sync) -> Duration {
+    let barrier = Barrier::new(threads);
+    let start = std::thread::scope(|s| {
+        for _ in 0..threads {
+            s.spawn(|| {
+                barrier.wait();
+                for _ in 0..iters {
+                    cycle();
+                }
+            });
+        }
+        Instant::now()
+    });
+    start.elapsed()
+}
+
+fn reserve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("reserve_release");
+    for threads in [1, 2, 4, 8] {
+        // the `RefCell`s behind `single-thread` can't be shared between threads
+        #[cfg(not(feature = "single-thread"))]
+        group.bench_with_input(BenchmarkId::new("lock_free", threads), &threads, |b, &threads| {
+            let slots = miniproto::shared_slots::SharedSlots::<u64>::new(64);
+            b.iter_custom(|iters| {
+                contended(threads, iters, || {
+                    let occupied = slots.reserve().unwrap().insert(black_box(1));
+                    black_box(occupied.take());
+                })
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("locked", threads), &threads, |b, &threads| {
+            let slots = locked::LockedSlots::new(64);
+            b.iter_custom(|iters| {
+                contended(threads, iters, || {
+                    let mut reserved = slots.reserve().unwrap();
+                    reserved.insert(black_box(1));
+                    black_box(reserved.take());
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, reserve);
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use miniproto::varint::{
+    decode_varint, decode_varint_len, decode_varints_fast, encode_varints, encoded_len, write_varint, write_varints,
+};
+use rand::Rng;
+
+fn decode(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let vals: Vec<u64> = (0..10_000).map(|_| rng.gen::<u64>() >> rng.gen_range(0..64)).collect();
+    let mut src = vec![];
+    encode_varints(&vals, &mut src);
+    let mut out = vec![0; vals.len()];
+
+    let mut group = c.benchmark_group("decode_varints");
+    group.bench_function("naive", |b| {
+        b.iter(|| {
+            let src = black_box(&src[..]);
+            let mut pos = 0;
+            for val in out.iter_mut() {
+                *val = decode_varint(&src[pos..]).unwrap();
+                pos += decode_varint_len(src[pos]);
+            }
+        })
+    });
+    group.bench_function("fast", |b| {
+        b.iter(|| decode_varints_fast(black_box(&src), &mut out).unwrap())
+    });
+    group.finish();
+}
+
+fn write(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let vals: Vec<u64> = (0..10_000).map(|_| rng.gen::<u64>() >> rng.gen_range(0..64)).collect();
+    let total: usize = vals.iter().map(|val| encoded_len(*val)).sum();
+    let mut dest = bytes::BytesMut::with_capacity(total);
+
+    let mut group = c.benchmark_group("write_varints");
+    group.bench_function("loop", |b| {
+        b.iter(|| {
+            dest.clear();
+            for val in black_box(&vals) {
+                write_varint(*val, &mut dest);
+            }
+        })
+    });
+    group.bench_function("batched", |b| {
+        b.iter(|| {
+            dest.clear();
+            write_varints(black_box(&vals), &mut dest);
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode, write);
+criterion_main!(benches);
@@ -0,0 +1,159 @@
+//! A [`tokio_util::codec`] for frames with a varint length prefix, the same layout as
+//! [`write_frame`](crate::varint::write_frame).
+
+use std::io;
+
+use bytes::{Buf, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::varint::{encoded_len, try_read_varint, write_varint};
+
+/// Encodes and decodes frames prefixed with their length as a varint.
+///
+/// Frames longer than the maximum length (see [`VarintLengthCodec::with_max_length`]) are rejected with
+/// [`io::ErrorKind::InvalidData`], so a peer can't make the decoder buffer an arbitrary amount of data.
+#[derive(Debug, Clone)]
+pub struct VarintLengthCodec {
+    max_length: usize,
+    // length of the frame being decoded, once its prefix has been read
+    pending: Option<usize>,
+}
+
+impl VarintLengthCodec {
+    /// The default maximum frame length, 8 MiB
+    pub const DEFAULT_MAX_LENGTH: usize = 8 * 1024 * 1024;
+
+    pub fn new() -> Self {
+        Self::with_max_length(Self::DEFAULT_MAX_LENGTH)
+    }
+
+    /// create a codec which rejects frames longer than `max_length`
+    pub fn with_max_length(max_length: usize) -> Self {
+        Self {
+            max_length,
+            pending: None,
+        }
+    }
+
+    fn too_long(&self, len: u64) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {len} bytes exceeds maximum length of {}", self.max_length),
+        )
+    }
+}
+
+impl Default for VarintLengthCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for VarintLengthCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let len = match self.pending {
+            Some(len) => len,
+            None => {
+                let Ok(len) = try_read_varint(src) else {
+                    // a partial prefix is left in `src` until the rest arrives
+                    return Ok(None);
+                };
+                if len > self.max_length as u64 {
+                    return Err(self.too_long(len));
+                }
+                self.pending = Some(len as usize);
+                len as usize
+            }
+        };
+        if src.remaining() < len {
+            src.reserve(len - src.remaining());
+            return Ok(None);
+        }
+        self.pending = None;
+        Ok(Some(src.split_to(len)))
+    }
+}
+
+impl Encoder<Bytes> for VarintLengthCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        if item.len() > self.max_length {
+            return Err(self.too_long(item.len() as u64));
+        }
+        dst.reserve(encoded_len(item.len() as u64) + item.len());
+        write_varint(item.len() as u64, dst);
+        dst.extend_from_slice(&item);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::AsyncWriteExt;
+    use tokio_util::codec::{FramedRead, FramedWrite};
+
+    use super::*;
+
+    fn frames() -> Vec<Bytes> {
+        [0, 1, 127, 128, 300, 20_000].iter().map(|len| Bytes::from(vec![*len as u8; *len])).collect()
+    }
+
+    #[test]
+    fn fragmented_decode() {
+        let mut encoded = BytesMut::new();
+        let mut codec = VarintLengthCodec::new();
+        for frame in frames() {
+            codec.encode(frame, &mut encoded).unwrap();
+        }
+
+        // feed the decoder one byte at a time, splitting every length prefix
+        let mut codec = VarintLengthCodec::new();
+        let mut src = BytesMut::new();
+        let mut decoded = vec![];
+        for byte in encoded {
+            src.extend_from_slice(&[byte]);
+            while let Some(frame) = codec.decode(&mut src).unwrap() {
+                decoded.push(frame.freeze());
+            }
+        }
+        assert_eq!(decoded, frames());
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn too_long() {
+        let mut codec = VarintLengthCodec::with_max_length(10);
+        let mut dst = BytesMut::new();
+        assert!(codec.encode(Bytes::from_static(&[0; 11]), &mut dst).is_err());
+        codec.encode(Bytes::from_static(&[0; 10]), &mut dst).unwrap();
+
+        let mut src = BytesMut::new();
+        write_varint(11, &mut src);
+        let err = codec.decode(&mut src).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn duplex() {
+        let (client, server) = tokio::io::duplex(64);
+        let writer = tokio::spawn(async move {
+            let mut sink = FramedWrite::new(client, VarintLengthCodec::new());
+            for frame in frames() {
+                sink.send(frame).await.unwrap();
+            }
+            sink.into_inner().shutdown().await.unwrap();
+        });
+
+        let decoded: Vec<Bytes> = FramedRead::new(server, VarintLengthCodec::new())
+            .map(|frame| frame.unwrap().freeze())
+            .collect()
+            .await;
+        writer.await.unwrap();
+        assert_eq!(decoded, frames());
+    }
+}
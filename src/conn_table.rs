@@ -0,0 +1,161 @@
+//! A connection registry handing out compact wire IDs.
+
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::shared_slots::{Occupied, SharedSlots};
+use crate::varint::{decode_varint_canonical, encode_varint, encoded_len};
+
+/// The wire ID of a connection in a [`ConnTable`]: its slot key and the slot's generation, packed into one varint.
+///
+/// The key takes the low bits, as many as the table's capacity needs, and the generation the rest. Each slot's
+/// generation moves on when its connection is closed, so a closed connection's ID doesn't find the next connection
+/// to use the slot. The generation wraps, so an ID can come back after the slot is reused that many times.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnId {
+    buf: [u8; 9],
+    len: u8,
+}
+
+impl ConnId {
+    fn new(id: u64) -> Self {
+        let mut buf = [0; 9];
+        let len = encode_varint(id, &mut buf) as u8;
+        Self { buf, len }
+    }
+
+    /// returns the encoded ID, as sent on the wire
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len as usize]
+    }
+}
+
+impl AsRef<[u8]> for ConnId {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl Debug for ConnId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("ConnId").field(&self.as_bytes()).finish()
+    }
+}
+
+/// A fixed-capacity table of connections, each identified by a [`ConnId`]
+pub struct ConnTable<T> {
+    slots: SharedSlots<T>,
+    // only changed while holding the slot, see `close`
+    generations: Vec<AtomicUsize>,
+    key_bits: u32,
+}
+
+impl<T> ConnTable<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: SharedSlots::new(capacity),
+            generations: (0..capacity).map(|_| AtomicUsize::new(0)).collect(),
+            key_bits: usize::BITS - capacity.saturating_sub(1).leading_zeros(),
+        }
+    }
+
+    /// Adds a connection, returns its ID or None if the table is full
+    pub fn register(&self, conn: T) -> Option<ConnId> {
+        let reserved = self.slots.reserve()?;
+        let id = self.encode(reserved.key());
+        reserved.insert(conn);
+        Some(id)
+    }
+
+    /// Finds a connection by its encoded ID. Malformed, overlong, unknown and closed IDs return None.
+    pub fn lookup(&self, id: &[u8]) -> Option<Occupied<'_, T>> {
+        let (key, generation) = self.decode(id)?;
+        let occupied = self.slots.get(key)?;
+        (self.generation(key) == generation).then_some(occupied)
+    }
+
+    /// Removes a connection by its encoded ID, returning it
+    pub fn close(&self, id: &[u8]) -> Option<T> {
+        let occupied = self.lookup(id)?;
+        let key = occupied.key();
+        let (conn, reserved) = occupied.take();
+        // the slot can't be reserved again until `reserved` is dropped, so the next connection gets the new generation
+        self.generations[key].fetch_add(1, Ordering::Relaxed);
+        drop(reserved);
+        Some(conn)
+    }
+
+    /// returns the generation of `key`, truncated to the bits left over in an ID
+    fn generation(&self, key: usize) -> u64 {
+        let generation = self.generations[key].load(Ordering::Relaxed) as u64;
+        generation & (u64::MAX >> self.key_bits)
+    }
+
+    fn encode(&self, key: usize) -> ConnId {
+        ConnId::new(self.generation(key) << self.key_bits | key as u64)
+    }
+
+    fn decode(&self, id: &[u8]) -> Option<(usize, u64)> {
+        let id_val = decode_varint_canonical(id).ok()?;
+        if id.len() != encoded_len(id_val) {
+            return None;
+        }
+        let key = (id_val & !(u64::MAX << self.key_bits)).try_into().ok()?;
+        Some((key, id_val >> self.key_bits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn register_and_lookup() {
+        let table = ConnTable::new(300);
+        let ids: Vec<ConnId> = (0..300).map(|i| table.register(i).unwrap()).collect();
+        assert!(table.register(300).is_none());
+        assert_eq!(ids[5].as_bytes(), [5]);
+        assert_eq!(ids[200].as_bytes().len(), 2);
+
+        for (i, id) in ids.iter().enumerate() {
+            let wire = id.as_bytes().to_vec();
+            assert_eq!(*table.lookup(&wire).unwrap(), i);
+        }
+
+        assert_eq!(table.close(ids[7].as_bytes()), Some(7));
+        assert!(table.lookup(ids[7].as_bytes()).is_none());
+        assert_eq!(table.close(ids[7].as_bytes()), None);
+    }
+
+    #[test]
+    fn closed_ids_stay_closed() {
+        let table = ConnTable::new(4);
+        let old = table.register("old").unwrap();
+        assert_eq!(table.close(old.as_bytes()), Some("old"));
+
+        // the slot is reused, the ID isn't
+        let new = table.register("new").unwrap();
+        assert_ne!(new, old);
+        assert!(table.lookup(old.as_bytes()).is_none());
+        assert_eq!(table.close(old.as_bytes()), None);
+        assert_eq!(*table.lookup(new.as_bytes()).unwrap(), "new");
+        assert_eq!(new.as_bytes(), [0b100]);
+    }
+
+    #[test]
+    fn malformed_ids() {
+        let table = ConnTable::new(4);
+        let id = table.register("a").unwrap();
+        assert!(table.lookup(&[]).is_none());
+        assert!(table.lookup(&[0b1000_0000]).is_none());
+        // overlong encoding of the same key
+        assert!(table.lookup(&[0b1000_0000, 0]).is_none());
+        // trailing bytes
+        assert!(table.lookup(&[id.as_bytes()[0], 0]).is_none());
+        // a generation the slot hasn't reached, and an empty slot
+        assert!(table.lookup(&[100]).is_none());
+        assert!(table.lookup(&[0xFF; 9]).is_none());
+        assert_eq!(*table.lookup(id.as_bytes()).unwrap(), "a");
+    }
+}
@@ -0,0 +1,72 @@
+//! Duplicate suppression for streams of frames carrying varint sequence numbers.
+
+use crate::varint::try_decode_varint;
+use crate::window::Window;
+use crate::Error;
+
+/// Decides whether each incoming frame is fresh, using a [`Window`] of the sequence numbers seen so far.
+///
+/// Each frame starts with its sequence number as a varint. Frames are accepted once, duplicates and frames too old for
+/// the window are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct DedupStream<const N: usize = 3> {
+    window: Window<N>,
+}
+
+impl<const N: usize> DedupStream<N> {
+    pub fn new() -> Self {
+        Self { window: Window::new() }
+    }
+
+    /// returns true if `seq` has not been accepted before and is not too old, recording it
+    pub fn accept(&mut self, seq: u64) -> bool {
+        self.window.insert(seq)
+    }
+
+    /// Reads the sequence number off the front of `frame`, returning the rest of the frame if it is fresh.
+    ///
+    /// Returns `Ok(None)` for duplicates, and [`Error::NeedMoreBytes`] if the frame is too short to hold a
+    /// sequence number.
+    pub fn accept_frame<'a>(&mut self, frame: &'a [u8]) -> Result<Option<&'a [u8]>, Error> {
+        let (seq, len) = try_decode_varint(frame)?;
+        Ok(self.accept(seq).then(|| &frame[len..]))
+    }
+
+    /// returns the window of accepted sequence numbers
+    pub fn window(&self) -> &Window<N> {
+        &self.window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::varint::encode_varint;
+
+    use super::*;
+
+    #[test]
+    fn out_of_order_duplicates() {
+        let mut stream = DedupStream::<3>::new();
+        let seqs = [0, 2, 1, 2, 5, 3, 0, 4, 5, 6, 1];
+        let accepted: Vec<u64> = seqs.iter().copied().filter(|seq| stream.accept(*seq)).collect();
+        assert_eq!(accepted, [0, 2, 1, 5, 3, 4, 6]);
+
+        // far enough ahead to slide the window, so 7 is too old
+        assert!(stream.accept(1000));
+        assert!(!stream.accept(7));
+    }
+
+    #[test]
+    fn frames() {
+        let mut stream = DedupStream::<3>::new();
+        let mut frame = [0; 12];
+        let len = encode_varint(456, &mut frame);
+        frame[len..len + 3].copy_from_slice(b"abc");
+        let frame = &frame[..len + 3];
+
+        assert_eq!(stream.accept_frame(frame), Ok(Some(&b"abc"[..])));
+        assert_eq!(stream.accept_frame(frame), Ok(None));
+        assert_eq!(stream.accept_frame(&[]), Err(Error::NeedMoreBytes { have: 0, need: 1 }));
+        assert_eq!(stream.accept_frame(&frame[..1]), Err(Error::NeedMoreBytes { have: 1, need: 2 }));
+    }
+}
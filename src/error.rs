@@ -0,0 +1,74 @@
+//! The error type shared by the crate's fallible functions.
+
+use core::fmt;
+
+/// Errors returned by the `try_*` functions and other fallible functions across the crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A source or destination buffer ended early, holding `have` of the `need` bytes required
+    NeedMoreBytes { have: usize, need: usize },
+    /// A length prefix declared `len` bytes, more than the `max` allowed
+    TooLong { len: u64, max: usize },
+    /// A varint was encoded with more bytes than necessary
+    NonCanonical,
+    /// A decoded value does not fit in the requested type
+    Overflow,
+    /// A length prefix is split across chunks that `Buf::chunks_vectored` doesn't expose, so it can't be read without
+    /// advancing the buffer
+    Fragmented,
+    /// Every slot in the pool is in use
+    PoolFull,
+    /// There's no item at the key
+    Vacant,
+    /// A thread panicked while holding the item at the key, see
+    /// [`SharedSlots::with_poisoning`](crate::shared_slots::SharedSlots::with_poisoning)
+    Poisoned,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NeedMoreBytes { have, need } => write!(f, "need {need} bytes, only {have} available"),
+            Error::TooLong { len, max } => write!(f, "length {len} exceeds the maximum of {max}"),
+            Error::NonCanonical => f.write_str("varint is not minimally encoded"),
+            Error::Overflow => f.write_str("value out of range"),
+            Error::Fragmented => f.write_str("length prefix split across hidden chunks"),
+            Error::PoolFull => f.write_str("every slot in the pool is in use"),
+            Error::Vacant => f.write_str("no item at key"),
+            Error::Poisoned => f.write_str("slot poisoned by a panic while it was held"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let cases = [
+            (Error::NeedMoreBytes { have: 3, need: 9 }, "need 9 bytes, only 3 available"),
+            (Error::TooLong { len: 1 << 40, max: 1024 }, "length 1099511627776 exceeds the maximum of 1024"),
+            (Error::NonCanonical, "varint is not minimally encoded"),
+            (Error::Overflow, "value out of range"),
+            (Error::Fragmented, "length prefix split across hidden chunks"),
+            (Error::PoolFull, "every slot in the pool is in use"),
+            (Error::Vacant, "no item at key"),
+            (Error::Poisoned, "slot poisoned by a panic while it was held"),
+        ];
+        for (err, msg) in cases {
+            assert_eq!(err.to_string(), msg);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn is_std_error() {
+        let err: Box<dyn std::error::Error + Send + Sync> = Box::new(Error::PoolFull);
+        assert!(err.source().is_none());
+        assert_eq!(err.to_string(), "every slot in the pool is in use");
+    }
+}
@@ -0,0 +1,161 @@
+//! Tracking of sent-but-unacknowledged items for retransmission.
+
+use alloc::boxed::Box;
+
+use crate::shared_slots::SharedSlots;
+use crate::window::Window;
+
+/// Holds sent items in a [`SharedSlots`] until they're acknowledged, recording acks in a [`Window`].
+///
+/// Sequence numbers are handed out from 0. At most `capacity` sequence numbers may be outstanding, counting from the
+/// oldest unacknowledged one, so a lost item stalls [`FlowWindow::send`] until it's acked. Nothing happens on a timer:
+/// the caller drives retransmission with [`FlowWindow::tick`].
+pub struct FlowWindow<T, const N: usize = 3> {
+    slots: SharedSlots<T>,
+    /// slot key of each unacked item, by `seq % capacity`
+    keys: Box<[usize]>,
+    acked: Window<N>,
+    /// oldest unacked sequence number
+    base: u64,
+    next: u64,
+}
+
+impl<T, const N: usize> FlowWindow<T, N> {
+    /// # Panics
+    ///
+    /// If `capacity` is 0, or too large for the window to hold every outstanding sequence number without sliding past
+    /// the oldest unacked one (more than `N - 1` words of `usize` bits).
+    pub fn new(capacity: usize) -> Self {
+        let acked = Window::new();
+        assert!(capacity > 0, "capacity must be at least 1");
+        assert!(
+            capacity + usize::BITS as usize <= acked.capacity(),
+            "capacity must leave a word of the window spare"
+        );
+        Self {
+            slots: SharedSlots::new(capacity),
+            keys: alloc::vec![usize::MAX; capacity].into_boxed_slice(),
+            acked,
+            base: 0,
+            next: 0,
+        }
+    }
+
+    /// returns the number of sequence numbers that may be outstanding at once
+    pub fn capacity(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// returns the oldest sequence number that hasn't been acked, or the next to be sent if all have
+    pub fn oldest_unacked(&self) -> u64 {
+        self.base
+    }
+
+    /// returns the sequence number the next [`FlowWindow::send`] will use
+    pub fn next_seq(&self) -> u64 {
+        self.next
+    }
+
+    /// Holds on to `item` until it's acked, returning its sequence number, or `None` if `capacity` sequence numbers are
+    /// already outstanding.
+    pub fn send(&mut self, item: T) -> Option<u64> {
+        if self.next - self.base >= self.capacity() as u64 {
+            return None;
+        }
+        let seq = self.next;
+        let reserved = self.slots.reserve().expect("one slot per outstanding sequence number");
+        self.keys[self.index(seq)] = reserved.insert(item).key();
+        self.next += 1;
+        Some(seq)
+    }
+
+    /// Acknowledges `seq`, dropping its item. Returns false if `seq` wasn't outstanding or was already acked.
+    pub fn ack(&mut self, seq: u64) -> bool {
+        if seq < self.base || seq >= self.next {
+            return false;
+        }
+        if self.acked.would_slide(seq) {
+            // only forgets acks below `base`, whose items are gone
+            self.acked.advance_to(self.base);
+        }
+        if !self.acked.insert(seq) {
+            return false;
+        }
+        self.slots.take(self.keys[self.index(seq)]);
+        while self.base < self.next && self.acked.contains(self.base) {
+            self.base += 1;
+        }
+        true
+    }
+
+    /// Calls `on_retransmit` with every item that's still unacked, oldest first.
+    pub fn tick(&self, mut on_retransmit: impl FnMut(u64, &T)) {
+        for seq in self.base..self.next {
+            if self.acked.contains(seq) {
+                continue;
+            }
+            let item = self.slots.get(self.keys[self.index(seq)]).expect("unacked items are held");
+            on_retransmit(seq, &item);
+        }
+    }
+
+    fn index(&self, seq: u64) -> usize {
+        (seq % self.capacity() as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unacked<T: Clone, const N: usize>(flow: &FlowWindow<T, N>) -> Vec<(u64, T)> {
+        let mut items = vec![];
+        flow.tick(|seq, item| items.push((seq, item.clone())));
+        items
+    }
+
+    #[test]
+    fn tick_skips_acked() {
+        let mut flow = FlowWindow::<char>::new(8);
+        for (i, c) in "abcdef".chars().enumerate() {
+            assert_eq!(flow.send(c), Some(i as u64));
+        }
+        assert!(flow.ack(1));
+        assert!(flow.ack(4));
+        assert!(!flow.ack(4));
+        assert!(!flow.ack(6));
+        assert_eq!(unacked(&flow), [(0, 'a'), (2, 'c'), (3, 'd'), (5, 'f')]);
+
+        assert!(flow.ack(0));
+        assert_eq!(flow.oldest_unacked(), 2);
+        assert!(!flow.ack(0));
+        assert!(flow.ack(2));
+        assert!(flow.ack(3));
+        assert!(flow.ack(5));
+        assert_eq!(flow.oldest_unacked(), 6);
+        assert_eq!(unacked(&flow), []);
+    }
+
+    #[test]
+    fn stalls_on_oldest_unacked() {
+        let mut flow = FlowWindow::<u64, 2>::new(64);
+        for seq in 0..64 {
+            assert_eq!(flow.send(seq), Some(seq));
+        }
+        assert_eq!(flow.send(64), None);
+        for seq in 1..64 {
+            assert!(flow.ack(seq));
+        }
+        assert_eq!(flow.send(64), None);
+        assert_eq!(unacked(&flow), [(0, 0)]);
+
+        // stream through many words of acks
+        assert!(flow.ack(0));
+        for seq in 64..1000 {
+            assert_eq!(flow.send(seq), Some(seq));
+            assert!(flow.ack(seq));
+        }
+        assert_eq!(flow.oldest_unacked(), 1000);
+        assert_eq!(unacked(&flow), []);
+    }
+}
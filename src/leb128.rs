@@ -0,0 +1,94 @@
+//! Unsigned LEB128, as used by WASM, DWARF and protobuf.
+//!
+//! This is *not* the format used by [`crate::varint`]. Each byte holds 7 bits of the value, least significant group
+//! first, and the high bit of every byte except the last is set. A `u64` takes at most 10 bytes.
+
+use crate::Error;
+
+/// The most bytes a `u64` can take as LEB128
+pub const MAX_LEN: usize = 10;
+
+/// Encode `val` as LEB128, returns the number of bytes written
+pub fn encode_leb128(mut val: u64, buf: &mut [u8]) -> usize {
+    let mut len = 0;
+    loop {
+        let byte = (val & 0x7F) as u8;
+        val >>= 7;
+        if val == 0 {
+            buf[len] = byte;
+            return len + 1;
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+}
+
+/// Decode a LEB128 value, returns the value and the number of bytes consumed.
+///
+/// Returns [`Error::Overflow`] if the value does not fit in a `u64`.
+pub fn decode_leb128(src: &[u8]) -> Result<(u64, usize), Error> {
+    let mut val = 0u64;
+    for (i, byte) in src.iter().enumerate().take(MAX_LEN) {
+        let bits = (*byte & 0x7F) as u64;
+        let shift = 7 * i as u32;
+        if i == MAX_LEN - 1 && bits > 1 {
+            return Err(Error::Overflow);
+        }
+        val |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok((val, i + 1));
+        }
+    }
+    if src.len() >= MAX_LEN {
+        return Err(Error::Overflow);
+    }
+    // the last byte read still had its continuation bit set
+    Err(Error::NeedMoreBytes { have: src.len(), need: src.len() + 1 })
+}
+
+#[cfg(test)]
+mod test {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    pub fn decode_knowns() {
+        assert_eq!(decode_leb128(&[0x00]), Ok((0, 1)));
+        assert_eq!(decode_leb128(&[0x7F]), Ok((127, 1)));
+        assert_eq!(decode_leb128(&[0x80, 0x01]), Ok((128, 2)));
+        assert_eq!(decode_leb128(&[0xE5, 0x8E, 0x26]), Ok((624485, 3)));
+        assert_eq!(decode_leb128(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]), Ok((u64::MAX, 10)));
+        // trailing bytes are left alone
+        assert_eq!(decode_leb128(&[0x80, 0x01, 0xFF]), Ok((128, 2)));
+    }
+
+    #[test]
+    pub fn decode_errors() {
+        assert_eq!(decode_leb128(&[]), Err(Error::NeedMoreBytes { have: 0, need: 1 }));
+        assert_eq!(decode_leb128(&[0xE5, 0x8E]), Err(Error::NeedMoreBytes { have: 2, need: 3 }));
+        assert_eq!(decode_leb128(&[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02]), Err(Error::Overflow));
+        assert_eq!(decode_leb128(&[0x80; 11]), Err(Error::Overflow));
+    }
+
+    #[test]
+    pub fn encode_knowns() {
+        let mut buf = [0; MAX_LEN];
+        let len = encode_leb128(624485, &mut buf);
+        assert_eq!(&buf[..len], [0xE5, 0x8E, 0x26]);
+        let len = encode_leb128(0, &mut buf);
+        assert_eq!(&buf[..len], [0]);
+    }
+
+    #[test]
+    pub fn roundtrips() {
+        let mut rng = rand::thread_rng();
+        let mut buf = [0; MAX_LEN];
+
+        for _ in 0..100_000 {
+            let val: u64 = rng.gen::<u64>() >> rng.gen_range(0..64);
+            let len = encode_leb128(val, &mut buf);
+            assert_eq!(decode_leb128(&buf[..len]), Ok((val, len)));
+        }
+    }
+}
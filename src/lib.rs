@@ -1,3 +1,27 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "spin", feature = "single-thread")))]
+compile_error!("`no_std` builds need the `spin` or `single-thread` feature for `SharedSlots`");
+
+extern crate alloc;
+
+mod lock;
+
+pub mod error;
+pub use error::Error;
+
 pub mod window;
 pub mod varint;
+pub mod leb128;
 pub mod shared_slots;
+pub mod dedup;
+pub mod conn_table;
+pub mod ordered_buffer;
+pub mod flow_window;
+#[cfg(feature = "bytes")]
+pub mod packet;
+#[cfg(feature = "codec")]
+pub mod codec;
+
+#[cfg(all(test, feature = "tracing"))]
+mod trace_capture;
@@ -0,0 +1,36 @@
+//! The mutex used by [`crate::shared_slots`]: `parking_lot` by default, a spinlock with the `spin` feature for
+//! `no_std` targets, or a `RefCell` with the `single-thread` feature.
+
+#[cfg(not(any(feature = "spin", feature = "single-thread")))]
+pub(crate) use parking_lot::{Mutex, MutexGuard};
+#[cfg(all(feature = "spin", not(feature = "single-thread")))]
+pub(crate) use spin::{Mutex, MutexGuard};
+#[cfg(feature = "single-thread")]
+pub(crate) use single_thread::{Mutex, MutexGuard};
+
+#[cfg(feature = "single-thread")]
+mod single_thread {
+    use core::cell::{RefCell, RefMut};
+
+    pub(crate) type MutexGuard<'a, T> = RefMut<'a, T>;
+
+    /// A `RefCell` with a mutex's interface. It's `!Sync`, so anything holding one stays on a single thread.
+    pub(crate) struct Mutex<T>(RefCell<T>);
+
+    impl<T> Mutex<T> {
+        pub(crate) const fn new(value: T) -> Self {
+            Self(RefCell::new(value))
+        }
+
+        /// # Panics
+        ///
+        /// If the value is already borrowed, where a real mutex would deadlock.
+        pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+            self.0.borrow_mut()
+        }
+
+        pub(crate) fn get_mut(&mut self) -> &mut T {
+            self.0.get_mut()
+        }
+    }
+}
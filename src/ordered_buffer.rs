@@ -0,0 +1,175 @@
+//! Reassembly of out-of-order frames into sequence order.
+
+use alloc::boxed::Box;
+use core::fmt;
+
+use crate::shared_slots::SharedSlots;
+use crate::window::Window;
+
+/// Why [`OrderedBuffer::push`] rejected a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushError {
+    /// The sequence number was already delivered
+    TooOld,
+    /// The sequence number is already buffered
+    Duplicate,
+    /// The sequence number is `capacity` or more past the next one to deliver, so its slot may still be needed
+    TooFarAhead,
+}
+
+impl fmt::Display for PushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PushError::TooOld => "sequence number was already delivered",
+            PushError::Duplicate => "sequence number is already buffered",
+            PushError::TooFarAhead => "sequence number is too far ahead of the buffer",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PushError {}
+
+/// Buffers frames that arrive out of order and hands them back in sequence order, starting from 0.
+///
+/// Frames live in a [`SharedSlots`], found through a table indexed by `seq % capacity`, and a [`Window`] records which
+/// sequence numbers have arrived. Only sequence numbers within `capacity` of the next one to deliver are accepted,
+/// so two live frames never share an index.
+pub struct OrderedBuffer<T, const N: usize = 3> {
+    slots: SharedSlots<T>,
+    /// slot key of each buffered frame, by `seq % capacity`. Only meaningful for sequence numbers in `arrived`
+    keys: Box<[usize]>,
+    arrived: Window<N>,
+    next: u64,
+}
+
+impl<T, const N: usize> OrderedBuffer<T, N> {
+    /// # Panics
+    ///
+    /// If `capacity` is 0, or too large for the window to hold a full buffer without sliding past the next sequence
+    /// number (more than `N - 1` words of `usize` bits).
+    pub fn new(capacity: usize) -> Self {
+        let arrived = Window::new();
+        assert!(capacity > 0, "capacity must be at least 1");
+        assert!(
+            capacity + usize::BITS as usize <= arrived.capacity(),
+            "capacity must leave a word of the window spare"
+        );
+        Self {
+            slots: SharedSlots::new(capacity),
+            keys: alloc::vec![usize::MAX; capacity].into_boxed_slice(),
+            arrived,
+            next: 0,
+        }
+    }
+
+    /// returns the sequence number [`OrderedBuffer::pop_next`] will deliver next
+    pub fn next_seq(&self) -> u64 {
+        self.next
+    }
+
+    /// returns the number of frames the buffer can hold
+    pub fn capacity(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Buffers `item` as frame `seq`, handing it back if it was rejected.
+    pub fn push(&mut self, seq: u64, item: T) -> Result<(), (PushError, T)> {
+        let Some(ahead) = seq.checked_sub(self.next) else {
+            return Err((PushError::TooOld, item));
+        };
+        if ahead >= self.capacity() as u64 {
+            return Err((PushError::TooFarAhead, item));
+        }
+        if self.arrived.would_slide(seq) {
+            // everything below `next` was delivered, so this only forgets frames that are gone. Afterwards the window
+            // spans the whole buffer, so inserting doesn't slide it
+            self.arrived.advance_to(self.next);
+        }
+        if !self.arrived.insert(seq) {
+            return Err((PushError::Duplicate, item));
+        }
+        let reserved = self.slots.reserve().expect("one slot per index in range");
+        self.keys[self.index(seq)] = reserved.insert(item).key();
+        Ok(())
+    }
+
+    /// Removes and returns the next frame in sequence, if it has arrived
+    pub fn pop_next(&mut self) -> Option<(u64, T)> {
+        let seq = self.next;
+        if !self.arrived.contains(seq) {
+            return None;
+        }
+        let item = self.slots.take(self.keys[self.index(seq)]).expect("arrived frames are buffered");
+        self.next += 1;
+        Some((seq, item))
+    }
+
+    fn index(&self, seq: u64) -> usize {
+        (seq % self.capacity() as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::SliceRandom, Rng};
+
+    use super::*;
+
+    #[test]
+    fn shuffled_drains_in_order() {
+        let mut rng = rand::thread_rng();
+        let mut buf = OrderedBuffer::<u64>::new(64);
+        let mut delivered = vec![];
+        // shuffle within blocks that fit the buffer, draining as frames become deliverable
+        for block in 0..50u64 {
+            let mut seqs: Vec<u64> = (block * 64..(block + 1) * 64).collect();
+            seqs.shuffle(&mut rng);
+            for seq in seqs {
+                buf.push(seq, seq * 10).unwrap();
+                while let Some((seq, item)) = buf.pop_next() {
+                    assert_eq!(item, seq * 10);
+                    delivered.push(seq);
+                }
+            }
+        }
+        assert_eq!(delivered, (0..50 * 64).collect::<Vec<_>>());
+        assert_eq!(buf.next_seq(), 50 * 64);
+    }
+
+    #[test]
+    fn rejections() {
+        let mut buf = OrderedBuffer::<&str>::new(4);
+        assert_eq!(buf.push(4, "collides with 0"), Err((PushError::TooFarAhead, "collides with 0")));
+        buf.push(1, "b").unwrap();
+        assert_eq!(buf.push(1, "again"), Err((PushError::Duplicate, "again")));
+        assert_eq!(buf.pop_next(), None);
+
+        buf.push(0, "a").unwrap();
+        assert_eq!(buf.pop_next(), Some((0, "a")));
+        assert_eq!(buf.pop_next(), Some((1, "b")));
+        assert_eq!(buf.push(0, "late"), Err((PushError::TooOld, "late")));
+
+        buf.push(5, "f").unwrap();
+        assert_eq!(buf.push(6, "g"), Err((PushError::TooFarAhead, "g")));
+    }
+
+    #[test]
+    fn jittered_arrivals_fill_small_window() {
+        let mut rng = rand::thread_rng();
+        // every frame arrives less than 32 places late, so none is ever 64 past the next one to deliver
+        let mut arrivals: Vec<(u64, u64)> = (0..5000).map(|seq| (seq + rng.gen_range(0..32), seq)).collect();
+        arrivals.sort();
+
+        let mut buf = OrderedBuffer::<u64, 2>::new(64);
+        let mut delivered = 0;
+        for (_, seq) in arrivals {
+            buf.push(seq, seq).unwrap();
+            while let Some((seq, item)) = buf.pop_next() {
+                assert_eq!((seq, item), (delivered, delivered));
+                delivered += 1;
+            }
+        }
+        assert_eq!(delivered, 5000);
+    }
+}
@@ -0,0 +1,137 @@
+//! A minimal packet header built from varint fields.
+
+use bytes::{Buf, BufMut};
+
+use crate::varint::{decode_varint_progress, peek_bytes, try_read_varint, write_varint};
+use crate::Error;
+
+/// The most bytes a [`PacketHeader`] takes on the wire
+pub const MAX_HEADER_LEN: usize = 9 + 9 + 1;
+
+/// A packet header, laid out as:
+///
+/// ```text
+/// [conn_id varint (1-9 bytes)] [seq varint (1-9 bytes)] [flags (1 byte)]
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketHeader {
+    pub conn_id: u64,
+    pub seq: u64,
+    pub flags: u8,
+}
+
+impl PacketHeader {
+    pub fn encode(&self, dest: &mut impl BufMut) {
+        write_varint(self.conn_id, dest);
+        write_varint(self.seq, dest);
+        dest.put_u8(self.flags);
+    }
+
+    /// Decode a header, advancing `src` past it.
+    ///
+    /// Returns [`Error::NeedMoreBytes`] without advancing if `src` does not hold a complete header. Only the bytes
+    /// visible through [`Buf::chunks_vectored`] can be checked before advancing: if the header runs past them, it's
+    /// read a field at a time, and running out there leaves `src` advanced past the fields already read.
+    pub fn decode(src: &mut impl Buf) -> Result<Self, Error> {
+        let mut buf = [0; MAX_HEADER_LEN];
+        let filled = peek_bytes(src, &mut buf);
+        match Self::decode_slice(&buf[..filled]) {
+            Ok((header, len)) => {
+                src.advance(len);
+                Ok(header)
+            }
+            Err(need) if filled == src.remaining() => Err(Error::NeedMoreBytes { have: filled, need }),
+            Err(_) => {
+                let conn_id = try_read_varint(src)?;
+                let seq = try_read_varint(src)?;
+                if !src.has_remaining() {
+                    return Err(Error::NeedMoreBytes { have: 0, need: 1 });
+                }
+                Ok(Self { conn_id, seq, flags: src.get_u8() })
+            }
+        }
+    }
+
+    /// Decode a header from the start of `src`, returns the header and its length, or `Err(needed_len)` like
+    /// [`decode_varint_progress`]
+    fn decode_slice(src: &[u8]) -> Result<(Self, usize), usize> {
+        let (conn_id, conn_id_len) = decode_varint_progress(src)?;
+        let (seq, seq_len) = decode_varint_progress(&src[conn_id_len..]).map_err(|need| conn_id_len + need)?;
+        let len = conn_id_len + seq_len;
+        let flags = *src.get(len).ok_or(len + 1)?;
+        Ok((Self { conn_id, seq, flags }, len + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut rng = rand::thread_rng();
+        let mut headers = vec![
+            PacketHeader { conn_id: 0, seq: 0, flags: 0 },
+            PacketHeader { conn_id: u64::MAX, seq: u64::MAX, flags: u8::MAX },
+        ];
+        for _ in 0..100 {
+            headers.push(PacketHeader {
+                conn_id: rng.gen::<u64>() >> rng.gen_range(0..64),
+                seq: rng.gen::<u64>() >> rng.gen_range(0..64),
+                flags: rng.gen(),
+            });
+        }
+
+        let mut buf = BytesMut::new();
+        for header in &headers {
+            header.encode(&mut buf);
+        }
+        let mut buf = buf.freeze();
+        for header in &headers {
+            assert_eq!(PacketHeader::decode(&mut buf), Ok(*header));
+        }
+        assert!(!buf.has_remaining());
+    }
+
+    #[test]
+    fn max_width() {
+        let mut buf = BytesMut::new();
+        PacketHeader { conn_id: u64::MAX, seq: u64::MAX, flags: 7 }.encode(&mut buf);
+        assert_eq!(buf.len(), MAX_HEADER_LEN);
+    }
+
+    #[test]
+    fn truncated() {
+        let mut buf = BytesMut::new();
+        PacketHeader { conn_id: 300, seq: 1 << 40, flags: 1 }.encode(&mut buf);
+        let needs = [1, 2, 3, 8, 8, 8, 8, 8, 9];
+        for (len, need) in needs.into_iter().enumerate() {
+            let mut src = &buf[..len];
+            assert_eq!(PacketHeader::decode(&mut src), Err(Error::NeedMoreBytes { have: len, need }));
+            assert_eq!(src.len(), len);
+        }
+    }
+
+    #[test]
+    fn hidden_chunks() {
+        use crate::varint::test::FirstChunk;
+
+        let header = PacketHeader { conn_id: 300, seq: 1 << 40, flags: 1 };
+        let mut buf = BytesMut::new();
+        header.encode(&mut buf);
+        header.encode(&mut buf);
+        let (front, back) = buf.split_at(1);
+        let mut hidden = FirstChunk(front.chain(back));
+        assert_eq!(PacketHeader::decode(&mut hidden), Ok(header));
+        assert_eq!(PacketHeader::decode(&mut hidden), Ok(header));
+        assert!(!hidden.has_remaining());
+
+        // ran out after reading both varints
+        let mut hidden_short = FirstChunk(front.chain(&back[..7]));
+        assert_eq!(PacketHeader::decode(&mut hidden_short), Err(Error::NeedMoreBytes { have: 0, need: 1 }));
+        assert!(!hidden_short.has_remaining());
+    }
+}
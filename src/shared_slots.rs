@@ -1,35 +1,99 @@
-use std::{
+use alloc::vec::Vec;
+use core::{
+    marker::PhantomData,
     ops::{Deref, DerefMut},
-    mem,
+    sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
 };
 
-use parking_lot::{Mutex, MutexGuard};
+use crate::lock::{Mutex, MutexGuard};
+use crate::Error;
 
 enum Slot<T> {
     Occupied(T),
-    Vacant { next: usize },
+    Vacant,
 }
 
+/// Bits of the free-list head holding the slot index, the rest hold a tag bumped on every push and pop, so a head
+/// that was popped and pushed back between a load and its compare-exchange no longer matches (the ABA problem).
+const INDEX_BITS: u32 = usize::BITS / 2;
+const INDEX_MASK: usize = (1 << INDEX_BITS) - 1;
+
+fn pack_head(tag: usize, index: usize) -> usize {
+    (tag << INDEX_BITS) | index
+}
+
+fn unpack_head(head: usize) -> (usize, usize) {
+    (head >> INDEX_BITS, head & INDEX_MASK)
+}
+
+/// On the free list and free to reserve
+const FREE: u8 = 0;
+/// Still on the free list, but reserved by [`SharedSlots::reserve_near`]. Slots can't be unlinked from the middle of
+/// the list, so the entry stays until it's popped, which just hands the slot to whoever claimed it.
+const CLAIMED: u8 = 1;
+/// Off the free list and in use
+const TAKEN: u8 = 2;
+
+/// How far past the hint [`SharedSlots::reserve_near`] looks
+const NEAR: usize = 8;
+
+/// A fixed number of slots that can be reserved, filled, read and emptied from many threads at once.
+///
+/// # Thread safety
+///
+/// `SharedSlots<T>` is `Send` and `Sync` when `T: Send`, and every method takes `&self`, so it can be shared between
+/// threads directly (e.g. with `std::thread::scope`) or behind an `Arc`. Reserving takes no shared lock, and each slot
+/// is behind its own lock, so operations on different keys run concurrently. [`SharedSlots::get`] and
+/// [`SharedSlots::take`] wait while another thread holds a guard for the same key.
+///
+/// The guards, [`Reserved`] and [`Occupied`], hold that lock and must be dropped on the thread that created them, so
+/// they are never `Send`. They are `Sync` when `T: Send + Sync`, letting other threads read through a shared
+/// reference to the guard.
+///
+/// With the `single-thread` feature the locks are `RefCell`s, so neither `SharedSlots` nor its guards are `Sync`, and
+/// looking up a key while a guard for it is held panics instead of waiting.
 pub struct SharedSlots<T> {
     slots: Vec<Mutex<Slot<T>>>,
-    next_free: Mutex<usize>,
+    /// the next free slot after each free slot, or `slots.len()` at the end of the list. Kept outside the slot
+    /// mutexes so popping never waits on a slot lock
+    next: Vec<AtomicUsize>,
+    /// tagged index of the first free slot, see [`INDEX_BITS`]
+    head: AtomicUsize,
+    /// each slot's [`FREE`], [`CLAIMED`] or [`TAKEN`] state
+    state: Vec<AtomicU8>,
+    /// whether each slot was poisoned, empty unless made with [`SharedSlots::with_poisoning`]
+    poisoned: Vec<AtomicBool>,
+    /// one permit per free slot, taken before a slot is reserved and returned once it's back on the free list
+    #[cfg(feature = "async")]
+    permits: tokio::sync::Semaphore,
 }
 
 struct SlotRef<'a, T> {
     slots: &'a SharedSlots<T>,
     slot: MutexGuard<'a, Slot<T>>,
     key: usize,
+    /// keeps guards `!Send` whichever lock backs them, `spin` guards would otherwise be `Send`
+    _not_send: PhantomData<*const ()>,
 }
 
+// SAFETY: a shared `SlotRef` only hands out `&T` and `&SharedSlots<T>`, which need `T: Sync` and `T: Send`. With
+// `single-thread` the `SharedSlots` isn't `Sync` at all, so neither are its guards.
+#[cfg(not(feature = "single-thread"))]
+unsafe impl<T: Send + Sync> Sync for SlotRef<'_, T> {}
+
 impl<T> Drop for SlotRef<'_, T> {
     fn drop(&mut self) {
-        let mut next_free = MutexGuard::unlocked(&mut self.slot, || self.slots.next_free.lock());
-        match &mut *self.slot {
-            Slot::Vacant { next } => {
-                *next = mem::replace(&mut *next_free, self.key);
+        #[cfg(feature = "std")]
+        if let (Slot::Occupied(_), Some(poisoned)) = (&*self.slot, self.slots.poisoned.get(self.key)) {
+            if std::thread::panicking() {
+                poisoned.store(true, Ordering::Relaxed);
             }
-            _ => {}
-        };
+        }
+        if let Slot::Vacant = &*self.slot {
+            self.slots.release(self.key);
+            #[cfg(feature = "async")]
+            self.slots.permits.add_permits(1);
+        }
     }
 }
 
@@ -40,6 +104,8 @@ impl<'a, T> Reserved<'a, T> {
         self.0.key
     }
     pub fn insert(mut self, item: T) -> Occupied<'a, T> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(key = self.0.key, "inserted into slot");
         *self.0.slot = Slot::Occupied(item);
         Occupied(self.0)
     }
@@ -53,7 +119,9 @@ impl<'a, T> Occupied<'a, T> {
     }
     pub fn take(self) -> (T, Reserved<'a, T>) {
         let mut inner = self.0;
-        let item = match std::mem::replace(&mut *inner.slot, Slot::Vacant { next: usize::MAX }) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(key = inner.key, "took from slot");
+        let item = match core::mem::replace(&mut *inner.slot, Slot::Vacant) {
             Slot::Occupied(item) => item,
             _ => unreachable!(),
         };
@@ -82,71 +150,242 @@ impl<T> DerefMut for Occupied<'_, T> {
 }
 
 impl<T> SharedSlots<T> {
+    /// # Panics
+    ///
+    /// If `capacity` doesn't fit in the index half of a `usize`.
     pub fn new(capacity: usize) -> Self {
-        let slots = std::iter::repeat(())
-            .enumerate()
-            .map(|(i, _)| Mutex::new(Slot::Vacant { next: i + 1 }))
-            .take(capacity)
-            .collect();
+        assert!(capacity <= INDEX_MASK, "capacity must be at most {INDEX_MASK}");
+        let slots = core::iter::repeat_with(|| Mutex::new(Slot::Vacant)).take(capacity).collect();
+        let next = (1..=capacity).map(AtomicUsize::new).collect();
 
         Self {
             slots,
-            next_free: Mutex::new(0),
+            next,
+            head: AtomicUsize::new(pack_head(0, 0)),
+            state: core::iter::repeat_with(|| AtomicU8::new(FREE)).take(capacity).collect(),
+            poisoned: Vec::new(),
+            #[cfg(feature = "async")]
+            permits: tokio::sync::Semaphore::new(capacity),
         }
     }
 
-    fn lock_slot(&self, key: usize) -> Option<SlotRef<'_, T>> {
-        let slot = self.slots.get(key)?.lock();
-        Some(SlotRef {
+    /// Like [`SharedSlots::new`], but a panic while an [`Occupied`] guard is held poisons its slot, like a
+    /// `std::sync::Mutex`. [`SharedSlots::get`] and [`SharedSlots::take`] then find nothing there, and
+    /// [`SharedSlots::try_get`] and [`SharedSlots::try_take`] return [`Error::Poisoned`], until
+    /// [`SharedSlots::clear_poison`] is called. Panics while holding a [`Reserved`] guard just free the slot.
+    #[cfg(feature = "std")]
+    pub fn with_poisoning(capacity: usize) -> Self {
+        let mut slots = Self::new(capacity);
+        slots.poisoned = core::iter::repeat_with(|| AtomicBool::new(false)).take(capacity).collect();
+        slots
+    }
+
+    /// returns true if a panic poisoned the slot at `key`, see [`SharedSlots::with_poisoning`]
+    pub fn is_poisoned(&self, key: usize) -> bool {
+        self.poisoned.get(key).is_some_and(|poisoned| poisoned.load(Ordering::Relaxed))
+    }
+
+    /// Makes a poisoned slot's item reachable again, returning true if it was poisoned
+    pub fn clear_poison(&self, key: usize) -> bool {
+        self.poisoned.get(key).is_some_and(|poisoned| poisoned.swap(false, Ordering::Relaxed))
+    }
+
+    /// Locks an occupied, unpoisoned slot. Vacant slots are unlocked without being wrapped in a [`SlotRef`], which
+    /// would push them onto the free list a second time when dropped.
+    fn lock_occupied(&self, key: usize) -> Result<SlotRef<'_, T>, Error> {
+        let slot = self.slots.get(key).ok_or(Error::Vacant)?.lock();
+        if let Slot::Vacant = &*slot {
+            return Err(Error::Vacant);
+        };
+        if self.is_poisoned(key) {
+            return Err(Error::Poisoned);
+        }
+        Ok(SlotRef {
             slots: self,
             slot,
             key,
+            _not_send: PhantomData,
         })
     }
 
     pub fn reserve(&self) -> Option<Reserved<'_, T>> {
-        let mut next_free = self.next_free.lock();
-        let key = *next_free;
-        let slot = self
-            .slots
-            .get(key)?
-            .lock();
-        let slot = SlotRef {
+        #[cfg(feature = "async")]
+        self.permits.try_acquire().ok()?.forget();
+        self.pop_free()
+    }
+
+    /// Waits until a slot is free, then reserves it
+    #[cfg(feature = "async")]
+    pub async fn reserve_async(&self) -> Reserved<'_, T> {
+        self.permits.acquire().await.expect("semaphore is never closed").forget();
+        self.pop_free().expect("a permit is only available while a slot is free")
+    }
+
+    /// Reserves a free slot at `hint` or up to 7 keys past it, the lowest first. If they're all in use (or past the end
+    /// of the pool) this falls back to [`SharedSlots::reserve`], which picks the most recently freed slot.
+    pub fn reserve_near(&self, hint: usize) -> Option<Reserved<'_, T>> {
+        #[cfg(feature = "async")]
+        self.permits.try_acquire().ok()?.forget();
+        let len = self.slots.len();
+        for key in hint.min(len)..hint.saturating_add(NEAR).min(len) {
+            if self.state[key].compare_exchange(FREE, CLAIMED, Ordering::AcqRel, Ordering::Relaxed).is_ok() {
+                return Some(self.lock_reserved(key));
+            }
+        }
+        self.pop_free()
+    }
+
+    fn pop_free(&self) -> Option<Reserved<'_, T>> {
+        loop {
+            let key = self.unlink_head()?;
+            // a claimed slot now belongs to its claimer, who pushes it back once it's vacant again
+            if self.state[key].swap(TAKEN, Ordering::AcqRel) == FREE {
+                return Some(self.lock_reserved(key));
+            }
+        }
+    }
+
+    fn unlink_head(&self) -> Option<usize> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let (tag, key) = unpack_head(head);
+            // a stale read is harmless: if `key` was popped since `head` was loaded, the tag no longer matches
+            let next = self.next.get(key)?.load(Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                pack_head(tag.wrapping_add(1), next),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(key),
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    fn lock_reserved(&self, key: usize) -> Reserved<'_, T> {
+        // only a lookup that finds the slot vacant, or the previous holder on its way out, can be holding this lock
+        let slot = self.slots[key].lock();
+        debug_assert!(matches!(&*slot, Slot::Vacant));
+        #[cfg(feature = "tracing")]
+        tracing::debug!(key, "reserved slot");
+        Reserved(SlotRef {
             slots: self,
             slot,
             key,
-        };
-        *next_free = match &*slot.slot {
-            Slot::Vacant { next } => *next,
-            _ => unreachable!(),
-        };
-        return Some(Reserved(slot));
+            _not_send: PhantomData,
+        })
+    }
+
+    fn release(&self, key: usize) {
+        // a claimed slot never left the free list, so freeing it is enough
+        if self.state[key].compare_exchange(CLAIMED, FREE, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            self.state[key].store(FREE, Ordering::Release);
+            self.push_free(key);
+        }
+    }
+
+    fn push_free(&self, key: usize) {
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let (tag, next) = unpack_head(head);
+            self.next[key].store(next, Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                pack_head(tag.wrapping_add(1), key),
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    /// returns the number of slots
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Drop vacant slots past `min_capacity`, returning the new capacity.
+    ///
+    /// Occupied slots are never dropped, so this stops short of `min_capacity` at the slot after the highest occupied
+    /// key. The free list is rebuilt lowest key first.
+    pub fn shrink_to(&mut self, min_capacity: usize) -> usize {
+        let occupied_len = self
+            .slots
+            .iter_mut()
+            .rposition(|slot| matches!(slot.get_mut(), Slot::Occupied(_)))
+            .map_or(0, |key| key + 1);
+        let len = min_capacity.max(occupied_len).min(self.slots.len());
+        self.slots.truncate(len);
+        self.slots.shrink_to_fit();
+        self.next.truncate(len);
+        self.next.shrink_to_fit();
+        self.state.truncate(len);
+        self.state.shrink_to_fit();
+        self.poisoned.truncate(len);
+        self.poisoned.shrink_to_fit();
+
+        let mut head = len;
+        for key in (0..len).rev() {
+            if let Slot::Vacant = self.slots[key].get_mut() {
+                *self.next[key].get_mut() = head;
+                *self.state[key].get_mut() = FREE;
+                head = key;
+            } else {
+                *self.state[key].get_mut() = TAKEN;
+            }
+        }
+        let (tag, _) = unpack_head(*self.head.get_mut());
+        *self.head.get_mut() = pack_head(tag.wrapping_add(1), head);
+        #[cfg(feature = "async")]
+        {
+            // `&mut self` means no permits are held and nobody is waiting for one
+            let vacant: usize = self.slots.iter_mut().map(|slot| matches!(slot.get_mut(), Slot::Vacant) as usize).sum();
+            self.permits = tokio::sync::Semaphore::new(vacant);
+        }
+        len
     }
 
     pub fn get(&self, key: usize) -> Option<Occupied<'_, T>> {
-        let slot = self.lock_slot(key)?;
-        if let Slot::Vacant { .. } = &*slot.slot {
-            return None;
-        };
-        Some(Occupied(slot))
+        self.try_get(key).ok()
     }
 
     pub fn take(&self, key: usize) -> Option<T> {
-        let slot = self.lock_slot(key)?;
-        if let Slot::Vacant { .. } = &*slot.slot {
-            return None;
-        };
-        Some(Occupied(slot).take().0)
+        self.try_take(key).ok()
+    }
+
+    /// Take the item at each of `keys` in turn, returning them in the same order with `None` for keys that are vacant,
+    /// out of range, or poisoned. Keys repeated in `keys` are taken once.
+    ///
+    /// Only one slot is locked at a time, so other threads can see some of the items taken and not others.
+    pub fn take_many(&self, keys: &[usize]) -> Vec<Option<T>> {
+        keys.iter().map(|key| self.take(*key)).collect()
+    }
+
+    /// Like [`SharedSlots::get`], returning [`Error::Vacant`] or [`Error::Poisoned`] when there's no item to get
+    pub fn try_get(&self, key: usize) -> Result<Occupied<'_, T>, Error> {
+        Ok(Occupied(self.lock_occupied(key)?))
+    }
+
+    /// Like [`SharedSlots::take`], returning [`Error::Vacant`] or [`Error::Poisoned`] when there's no item to take
+    pub fn try_take(&self, key: usize) -> Result<T, Error> {
+        Ok(Occupied(self.lock_occupied(key)?).take().0)
     }
 
     pub fn insert(&self, item: T) -> Option<usize> {
         Some(self.reserve()?.insert(item).key())
     }
+
+    /// Like [`SharedSlots::insert`], returning [`Error::PoolFull`] when every slot is in use
+    pub fn try_insert(&self, item: T) -> Result<usize, Error> {
+        self.insert(item).ok_or(Error::PoolFull)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use rand::Rng;
     use std::collections::HashSet;
 
     use super::*;
@@ -163,6 +402,7 @@ mod tests {
         assert_eq!(slots.get(4).as_deref(), Some(&5));
         assert_eq!(slots.insert(10), Some(3));
         assert_eq!(slots.get(3).as_deref(), Some(&10));
+        assert_eq!(slots.try_insert(11), Err(Error::PoolFull));
     }
 
     #[test]
@@ -188,6 +428,249 @@ mod tests {
         assert_eq!(key2, slot2.key());
     }
 
+    #[test]
+    fn vacant_lookups() {
+        let slots = SharedSlots::<i32>::new(2);
+        let key = slots.insert(1).unwrap();
+        assert_eq!(slots.take(key), Some(1));
+        assert!(slots.get(key).is_none());
+        assert!(slots.take(key).is_none());
+
+        let a = slots.reserve().unwrap();
+        let b = slots.reserve().unwrap();
+        assert_ne!(a.key(), b.key());
+        assert!(slots.reserve().is_none());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test(flavor = "current_thread")]
+    async fn reserve_async_waits() {
+        let slots = SharedSlots::<usize>::new(3);
+        let mut keys = vec![];
+        for i in 0..3 {
+            keys.push(slots.reserve_async().await.insert(i).key());
+        }
+        assert!(slots.reserve().is_none());
+
+        let waiter = slots.reserve_async();
+        tokio::pin!(waiter);
+        assert!(futures::poll!(&mut waiter).is_pending());
+
+        let (freed, ()) = tokio::join!(waiter, async {
+            tokio::task::yield_now().await;
+            assert_eq!(slots.take(keys[1]), Some(1));
+        });
+        assert_eq!(freed.key(), keys[1]);
+        drop(freed);
+
+        let reserved = slots.reserve_async().await;
+        assert_eq!(reserved.key(), keys[1]);
+        assert!(slots.reserve().is_none());
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn traces_lifecycle() {
+        use tracing::Level;
+
+        let events = crate::trace_capture::capture(|| {
+            let slots = SharedSlots::<i32>::new(2);
+            slots.reserve().unwrap();
+            let key = slots.insert(7).unwrap();
+            assert_eq!(slots.take(key), Some(7));
+        });
+        let expected = [
+            "reserved slot key=0",
+            "reserved slot key=0",
+            "inserted into slot key=0",
+            "took from slot key=0",
+        ];
+        assert_eq!(events, expected.map(|event| (Level::DEBUG, event.to_string())));
+    }
+
+    #[test]
+    fn shrink_to() {
+        let mut slots = SharedSlots::<usize>::new(16);
+        for i in 0..16 {
+            assert_eq!(slots.insert(i * 10), Some(i));
+        }
+        for key in [3, 5, 10, 11, 12, 13, 14, 15] {
+            assert_eq!(slots.take(key), Some(key * 10));
+        }
+
+        // 9 is still occupied
+        assert_eq!(slots.shrink_to(4), 10);
+        assert_eq!(slots.capacity(), 10);
+        for key in [0, 1, 2, 4, 6, 7, 8, 9] {
+            assert_eq!(slots.get(key).as_deref(), Some(&(key * 10)));
+        }
+        assert!(slots.get(10).is_none());
+
+        let reserved: Vec<_> = std::iter::from_fn(|| slots.reserve()).collect();
+        assert_eq!(reserved.iter().map(Reserved::key).collect::<Vec<_>>(), [3, 5]);
+        drop(reserved);
+
+        assert_eq!(slots.take(9), Some(90));
+        assert_eq!(slots.take(8), Some(80));
+        assert_eq!(slots.shrink_to(0), 8);
+        assert_eq!(slots.shrink_to(100), 8);
+        let keys = HashSet::<usize>::from_iter(std::iter::from_fn(|| slots.insert(0)));
+        assert_eq!(keys, HashSet::from([3, 5]));
+    }
+
+    #[cfg(all(feature = "std", not(feature = "single-thread")))]
+    #[test]
+    fn poisoned_by_panic() {
+        let slots = SharedSlots::<Vec<u32>>::with_poisoning(2);
+        let key = slots.insert(vec![1, 2]).unwrap();
+        let other = slots.insert(vec![3]).unwrap();
+        let result = std::thread::scope(|s| {
+            s.spawn(|| {
+                let mut items = slots.get(key).unwrap();
+                items.push(3);
+                panic!("mid-mutation");
+            })
+            .join()
+        });
+        assert!(result.is_err());
+
+        assert!(slots.is_poisoned(key));
+        assert!(matches!(slots.try_get(key), Err(Error::Poisoned)));
+        assert_eq!(slots.try_take(key), Err(Error::Poisoned));
+        assert!(slots.get(key).is_none());
+        assert_eq!(slots.get(other).as_deref(), Some(&vec![3]));
+
+        assert!(slots.clear_poison(key));
+        assert!(!slots.clear_poison(key));
+        assert_eq!(slots.take(key), Some(vec![1, 2, 3]));
+        assert_eq!(slots.try_take(key), Err(Error::Vacant));
+    }
+
+    #[cfg(all(feature = "std", not(feature = "single-thread")))]
+    #[test]
+    fn poisoning_is_opt_in() {
+        let slots = SharedSlots::<u32>::new(1);
+        let key = slots.insert(1).unwrap();
+        let result = std::thread::scope(|s| {
+            s.spawn(|| {
+                let _item = slots.get(key).unwrap();
+                panic!("holding an occupied slot");
+            })
+            .join()
+        });
+        assert!(result.is_err());
+        assert!(!slots.is_poisoned(key));
+        assert_eq!(slots.take(key), Some(1));
+
+        // a reserved slot just goes back on the free list
+        let slots = SharedSlots::<u32>::with_poisoning(1);
+        let result = std::thread::scope(|s| {
+            s.spawn(|| {
+                let _reserved = slots.reserve().unwrap();
+                panic!("holding a reserved slot");
+            })
+            .join()
+        });
+        assert!(result.is_err());
+        let key = slots.insert(2).unwrap();
+        assert!(!slots.is_poisoned(key));
+        assert_eq!(slots.take(key), Some(2));
+    }
+
+    #[test]
+    fn reserve_near_hint() {
+        let slots = SharedSlots::<usize>::new(32);
+        let reserved = slots.reserve_near(20).unwrap();
+        assert_eq!(reserved.key(), 20);
+        let key = reserved.insert(20).key();
+
+        // 20 is taken, so the next one up
+        assert_eq!(slots.reserve_near(20).unwrap().insert(21).key(), 21);
+        assert_eq!(slots.reserve_near(31).unwrap().key(), 31);
+
+        // the claimed entries are skipped when they reach the head of the free list
+        let keys: Vec<usize> = std::iter::from_fn(|| Some(slots.reserve()?.insert(0).key())).collect();
+        assert_eq!(keys.len(), 30);
+        assert!(!keys.contains(&20) && !keys.contains(&21));
+        assert!(slots.reserve_near(0).is_none());
+
+        // nothing free near the hint falls back to the free list
+        assert_eq!(slots.take(5), Some(0));
+        assert_eq!(slots.reserve_near(20).unwrap().key(), 5);
+        assert_eq!(slots.reserve_near(100).unwrap().key(), 5);
+        assert_eq!(slots.take(key), Some(20));
+        assert_eq!(slots.reserve_near(18).unwrap().key(), 20);
+    }
+
+    #[cfg(not(feature = "single-thread"))]
+    #[test]
+    fn reserve_near_threaded() {
+        let slots = SharedSlots::<usize>::new(16);
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let slots = &slots;
+                s.spawn(move || {
+                    for i in 0..20000 {
+                        let near = slots.reserve_near((t * 4 + i) % 16).unwrap().insert(t);
+                        let other = slots.reserve().unwrap();
+                        assert_ne!(near.key(), other.key());
+                        if i % 3 == 0 {
+                            let key = near.key();
+                            drop(near);
+                            assert_eq!(slots.take(key), Some(t));
+                        } else {
+                            drop(near.take());
+                        }
+                    }
+                });
+            }
+        });
+        let keys = HashSet::<usize>::from_iter(std::iter::from_fn(|| slots.insert(0)));
+        assert_eq!(keys, HashSet::from_iter(0..16));
+    }
+
+    #[test]
+    fn take_many() {
+        let slots = SharedSlots::<i32>::new(6);
+        for i in 0..4 {
+            slots.insert(i);
+        }
+        assert_eq!(slots.take(2), Some(2));
+        assert_eq!(
+            slots.take_many(&[3, 2, 0, 0, 9, 5]),
+            [Some(3), None, Some(0), None, None, None]
+        );
+        assert_eq!(slots.get(1).as_deref(), Some(&1));
+
+        let keys = HashSet::<usize>::from_iter(std::iter::from_fn(|| slots.insert(7)));
+        assert_eq!(keys, HashSet::from([0, 2, 3, 4, 5]));
+    }
+
+    #[cfg(feature = "single-thread")]
+    #[test]
+    fn single_thread() {
+        let slots = SharedSlots::<String>::new(2);
+        let reserved = slots.reserve().unwrap();
+        let key = reserved.key();
+        reserved.insert("a".to_string()).push('b');
+        assert_eq!(slots.get(key).as_deref().map(String::as_str), Some("ab"));
+        let other = slots.insert("c".to_string()).unwrap();
+        assert!(slots.reserve().is_none());
+        assert_eq!(slots.take(key).as_deref(), Some("ab"));
+        assert_eq!(slots.take(other).as_deref(), Some("c"));
+        assert!(slots.get(key).is_none());
+        assert_eq!(slots.reserve().unwrap().key(), other);
+    }
+
+    #[cfg(feature = "single-thread")]
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn single_thread_reentrant_lookup() {
+        let slots = SharedSlots::<u32>::new(1);
+        let occupied = slots.reserve().unwrap().insert(1);
+        let _again = slots.get(occupied.key());
+    }
+
     #[test]
     fn simple() {
         let slots = SharedSlots::<i32>::new(5);
@@ -201,12 +684,15 @@ mod tests {
             assert_eq!(*slots.get(i as usize).unwrap(), i)
         }
     }
+    #[cfg(not(feature = "single-thread"))]
     #[test]
     fn threaded() {
+        use rand::Rng;
+
         let slots = SharedSlots::<i32>::new(100);
         let mut values = vec![0i32; 100];
         rand::thread_rng().fill(&mut values[..]);
-        let values = HashSet::from_iter(values.into_iter());
+        let values = HashSet::from_iter(values);
 
         std::thread::scope(|s| {
             for i in values.iter() {
@@ -219,18 +705,19 @@ mod tests {
 
         let mut stored = HashSet::new();
         for i in 0..values.len() {
-            stored.insert(*slots.get(i as usize).unwrap());
+            stored.insert(*slots.get(i).unwrap());
         }
         assert_eq!(values, stored);
     }
 
+    #[cfg(not(feature = "single-thread"))]
     #[test]
     fn no_deadlock() {
         let slots = SharedSlots::<i32>::new(1);
-        let result = std::thread::scope(|s| {
+        std::thread::scope(|s| {
             let a = s.spawn(|| {
                 let mut successes = 0;
-                for i in 0..100000 {
+                for _ in 0..100000 {
                     if slots.reserve().is_some() {
                         successes += 1;
                     }
@@ -239,7 +726,7 @@ mod tests {
             });
             let b = s.spawn(|| {
                 let mut successes = 0;
-                for i in 0..100000 {
+                for _ in 0..100000 {
                     if slots.reserve().is_some() {
                         successes += 1;
                     }
@@ -250,13 +737,14 @@ mod tests {
         });
     }
 
+    #[cfg(not(feature = "single-thread"))]
     #[test]
     fn no_intefere() {
         let slots = SharedSlots::<i32>::new(2);
         let result = std::thread::scope(|s| {
             let a = s.spawn(|| {
                 let mut successes = 0;
-                for i in 0..100000 {
+                for _ in 0..100000 {
                     if slots.reserve().is_some() {
                         successes += 1;
                     }
@@ -265,7 +753,7 @@ mod tests {
             });
             let b = s.spawn(|| {
                 let mut successes = 0;
-                for i in 0..100000 {
+                for _ in 0..100000 {
                     if slots.reserve().is_some() {
                         successes += 1;
                     }
@@ -276,13 +764,65 @@ mod tests {
         });
         assert_eq!(result, 200000);
     }
+    #[cfg(not(feature = "single-thread"))]
+    #[test]
+    fn guards_shared_across_scoped_threads() {
+        let slots = SharedSlots::<String>::new(4);
+        let mut occupied = slots.reserve().unwrap().insert("a".to_string());
+        let key = occupied.key();
+        std::thread::scope(|s| {
+            let guard = &occupied;
+            s.spawn(move || assert_eq!(guard.as_str(), "a"));
+            s.spawn(|| {
+                let other = slots.insert("b".to_string()).unwrap();
+                assert_ne!(other, key);
+                assert_eq!(slots.take(other).as_deref(), Some("b"));
+            });
+        });
+        occupied.push('!');
+        drop(occupied);
+
+        std::thread::scope(|s| {
+            s.spawn(|| assert_eq!(slots.take(key).as_deref(), Some("a!")));
+        });
+    }
+
+    #[cfg(not(feature = "single-thread"))]
+    #[test]
+    fn free_list_stays_consistent() {
+        let slots = SharedSlots::<usize>::new(8);
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let slots = &slots;
+                s.spawn(move || {
+                    for i in 0..20000 {
+                        let a = slots.reserve().unwrap().insert(t);
+                        let b = slots.reserve().unwrap();
+                        assert_ne!(a.key(), b.key());
+                        if i % 2 == 0 {
+                            drop(a.take());
+                        } else {
+                            let key = a.key();
+                            drop(a);
+                            assert_eq!(slots.take(key), Some(t));
+                        }
+                    }
+                });
+            }
+        });
+
+        let keys = HashSet::<usize>::from_iter(std::iter::from_fn(|| Some(slots.reserve()?.insert(0).key())));
+        assert_eq!(keys, HashSet::from_iter(0..8));
+    }
+
+    #[cfg(not(feature = "single-thread"))]
     #[test]
     fn no_deadlock4() {
         let slots = SharedSlots::<i32>::new(2);
         let result = std::thread::scope(|s| {
             let a = s.spawn(|| {
                 let mut successes = 0;
-                for i in 0..100000 {
+                for _ in 0..100000 {
                     if slots.reserve().is_some() {
                         successes += 1;
                     }
@@ -291,7 +831,7 @@ mod tests {
             });
             let b = s.spawn(|| {
                 let mut successes = 0;
-                for i in 0..100000 {
+                for _ in 0..100000 {
                     if slots.reserve().is_some() {
                         successes += 1;
                     }
@@ -300,7 +840,7 @@ mod tests {
             });
             let c = s.spawn(|| {
                 let mut successes = 0;
-                for i in 0..100000 {
+                for _ in 0..100000 {
                     if slots.reserve().is_some() {
                         successes += 1;
                     }
@@ -309,7 +849,7 @@ mod tests {
             });
             let d = s.spawn(|| {
                 let mut successes = 0;
-                for i in 0..100000 {
+                for _ in 0..100000 {
                     if slots.reserve().is_some() {
                         successes += 1;
                     }
@@ -321,3 +861,29 @@ mod tests {
         assert!( result >= 100000 )
     }
 }
+
+/// Runs against the spinlock build, using only `core` and `alloc`
+#[cfg(all(test, not(feature = "std")))]
+mod no_std_smoke {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn reserve_insert_take() {
+        let slots = SharedSlots::<u32>::new(4);
+        let keys: Vec<usize> = (0..4).map(|i| slots.reserve().unwrap().insert(i).key()).collect();
+        assert!(slots.reserve().is_none());
+
+        assert_eq!(slots.take(keys[2]), Some(2));
+        assert!(slots.get(keys[2]).is_none());
+        let reserved = slots.reserve().unwrap();
+        assert_eq!(reserved.key(), keys[2]);
+        drop(reserved);
+
+        let (item, reserved) = slots.get(keys[0]).unwrap().take();
+        assert_eq!(item, 0);
+        assert_eq!(*reserved.insert(10), 10);
+        assert_eq!(slots.take(keys[0]), Some(10));
+    }
+}
@@ -52,15 +52,14 @@
 //! If the first byte is 0xFF, then the value bits of that byte can be ignored (masks to 0).
 //! simply read the next 8 bytes as a normal 64 bit integer.
 
-#[inline(always)]
-fn ceil_div(n: u32, d: u32) -> u32 {
-    (n + d - 1) / d
-}
+use alloc::vec::Vec;
+
+use crate::Error;
 
 /// Returns the length of a varint, given its most significant bit
 #[inline(always)]
 pub fn decode_varint_len(msb: u8) -> usize {
-    return msb.leading_ones() as usize + 1;
+    msb.leading_ones() as usize + 1
 }
 
 /// Decode a varint of known length. You should probably use [`read_varint`] or [`decode_varint`] instead.
@@ -73,33 +72,225 @@ pub fn decode_varint_unchecked(src: &[u8]) -> u64 {
         unreachable!("decode_varint_unchecked called with invalid length");
     }
     // mask for the most significant bits
-    let mut buf = [0; 8];
-    let offset = 8 - len;
+    let mut buf = [0; 9];
+    let offset = 9 - len;
     buf[offset..].copy_from_slice(src);
     buf[offset] &= (0xFFu16 >> len) as u8;
-    return u64::from_be_bytes(buf[1..].try_into().unwrap());
+    u64::from_be_bytes(buf[1..].try_into().unwrap())
 }
 
 /// Decode a varint, returns None if src does not have enough characters.
+///
+/// Overlong encodings (a small value encoded in more bytes than needed) are accepted, use
+/// [`decode_varint_canonical`] to reject them.
 pub fn decode_varint(src: &[u8]) -> Option<u64> {
-    let len = decode_varint_len(*src.get(0)?);
+    let len = decode_varint_len(*src.first()?);
     Some(decode_varint_unchecked(src.get(0..len)?))
 }
 
+/// Decode a varint, returns the value and the number of bytes consumed, or `Err(needed_len)` with the total number
+/// of bytes needed if `src` is too short.
+///
+/// An empty `src` needs at least one byte to determine the length, so returns `Err(1)`.
+pub fn decode_varint_progress(src: &[u8]) -> Result<(u64, usize), usize> {
+    let len = decode_varint_len(*src.first().ok_or(1usize)?);
+    let bytes = src.get(0..len).ok_or(len)?;
+    Ok((decode_varint_unchecked(bytes), len))
+}
+
+/// Decode a varint, rejecting overlong encodings with [`Error::NonCanonical`].
+///
+/// Every value has exactly one canonical encoding, which matters when encoded bytes are signed or hashed.
+pub fn decode_varint_canonical(src: &[u8]) -> Result<u64, Error> {
+    let (val, len) = try_decode_varint(src)?;
+    if encoded_len(val) != len {
+        return Err(Error::NonCanonical);
+    }
+    Ok(val)
+}
+
+/// Decode a varint from the start of `src`, returning the value and its length.
+///
+/// Like [`decode_varint_progress`], reporting how many bytes were missing through [`Error::NeedMoreBytes`]. Overlong
+/// encodings are accepted like [`decode_varint`] does, use [`decode_varint_canonical`] to reject them.
+pub fn try_decode_varint(src: &[u8]) -> Result<(u64, usize), Error> {
+    decode_at(src, 0)
+}
+
+/// Decode the varint at `pos` in `src`, counting [`Error::NeedMoreBytes`] from the start of `src`
+fn decode_at(src: &[u8], pos: usize) -> Result<(u64, usize), Error> {
+    decode_varint_progress(&src[pos..]).map_err(|need| Error::NeedMoreBytes { have: src.len(), need: pos + need })
+}
+
+/// Returns the length of the next varint in a [`bytes::Buf`] without advancing it, or None if the buffer is empty.
+#[cfg(feature = "bytes")]
+pub fn peek_varint_len(src: &impl bytes::Buf) -> Option<usize> {
+    Some(decode_varint_len(*src.chunk().first()?))
+}
+
 /// Read a varint from a [`bytes::Buf`], advancing the buffer
+///
+/// Varints split across chunks are handled. Returns None without advancing if the buffer does not hold a complete
+/// varint, use [`try_read_varint`] to find out how many bytes are missing.
+#[cfg(feature = "bytes")]
+pub fn read_varint(src: &mut impl bytes::Buf) -> Option<u64> {
+    let len = peek_varint_len(src)?;
+    let chunk = src.chunk();
+    if let Some(bytes) = chunk.get(..len) {
+        let val = decode_varint_unchecked(bytes);
+        src.advance(len);
+        return Some(val);
+    }
+    if src.remaining() < len {
+        return None;
+    }
+    let mut buf = [0; 9];
+    src.copy_to_slice(&mut buf[..len]);
+    Some(decode_varint_unchecked(&buf[..len]))
+}
+
+/// Returns the number of bytes [`encode_varint`] will use for `val`
+pub fn encoded_len(val: u64) -> usize {
+    let bitlen = u64::BITS - val.leading_zeros();
+    match bitlen.div_ceil(7) {
+        0..=1 => 1,
+        len @ 2..=8 => len as usize,
+        _ => 9,
+    }
+}
+
+/// Returns the length of the next varint in a [`bytes::Buf`], or [`Error::NeedMoreBytes`] if it isn't all there
+#[cfg(feature = "bytes")]
+fn buffered_varint_len(src: &impl bytes::Buf) -> Result<usize, Error> {
+    let have = src.remaining();
+    let need = peek_varint_len(src).unwrap_or(1);
+    if have < need {
+        return Err(Error::NeedMoreBytes { have, need });
+    }
+    Ok(need)
+}
+
+/// Read a varint from a [`bytes::Buf`], advancing the buffer.
+///
+/// Returns [`Error::NeedMoreBytes`] without advancing if the buffer does not hold a complete varint.
+#[cfg(feature = "bytes")]
+pub fn try_read_varint(src: &mut impl bytes::Buf) -> Result<u64, Error> {
+    let len = buffered_varint_len(src)?;
+    let mut buf = [0; 9];
+    src.copy_to_slice(&mut buf[..len]);
+    Ok(decode_varint_unchecked(&buf[..len]))
+}
+
+/// Read exactly `out.len()` varints from a [`bytes::Buf`], advancing the buffer past them.
+///
+/// Returns [`Error::NeedMoreBytes`] without advancing if the buffer holds fewer, counting the bytes up to the end of the
+/// first varint cut off, though `out` may have been partly overwritten. Only the bytes visible through [`bytes::Buf::chunks_vectored`] (up to 16 chunks) can be checked
+/// before advancing: if the varints run past them, the rest are read one at a time, and running out there leaves the
+/// buffer advanced past the varints already read.
+#[cfg(feature = "bytes")]
+pub fn read_varints_into(src: &mut impl bytes::Buf, out: &mut [u64]) -> Result<(), Error> {
+    // like `decode_varint_progress`, over an iterator
+    fn next_varint(bytes: &mut impl Iterator<Item = u8>) -> Result<(u64, usize), usize> {
+        let first = bytes.next().ok_or(1usize)?;
+        let len = decode_varint_len(first);
+        let mut buf = [first; 9];
+        for byte in &mut buf[1..len] {
+            *byte = bytes.next().ok_or(len)?;
+        }
+        Ok((decode_varint_unchecked(&buf[..len]), len))
+    }
+
+    let mut chunks = [std::io::IoSlice::new(&[]); 16];
+    let n = src.chunks_vectored(&mut chunks);
+    let visible: usize = chunks[..n].iter().map(|chunk| chunk.len()).sum();
+    let mut bytes = chunks[..n].iter().flat_map(|chunk| chunk.iter().copied());
+    let mut consumed = 0;
+    for (i, val) in out.iter_mut().enumerate() {
+        let (decoded, len) = match next_varint(&mut bytes) {
+            Ok(next) => next,
+            Err(need) if visible == src.remaining() => {
+                return Err(Error::NeedMoreBytes { have: visible, need: consumed + need });
+            }
+            Err(_) => {
+                src.advance(consumed);
+                for val in &mut out[i..] {
+                    *val = try_read_varint(src)?;
+                }
+                return Ok(());
+            }
+        };
+        *val = decoded;
+        consumed += len;
+    }
+    src.advance(consumed);
+    Ok(())
+}
+
+/// Advance a [`bytes::Buf`] past the next varint without decoding it, returns the number of bytes skipped.
+///
+/// Returns [`Error::NeedMoreBytes`] without advancing if the buffer does not hold a complete varint.
 #[cfg(feature = "bytes")]
-pub fn read_varint(src: &mut impl bytes::Buf) -> u64 {
-    let buf = src.chunk();
-    let len = decode_varint_len(buf[0]);
-    let val = decode_varint_unchecked(&buf[..len]);
+pub fn skip_varint(src: &mut impl bytes::Buf) -> Result<usize, Error> {
+    let len = buffered_varint_len(src)?;
     src.advance(len);
-    return val;
+    Ok(len)
+}
+
+/// An iterator reading varints from a [`bytes::Buf`] until it is exhausted.
+///
+/// A truncated varint at the end of the buffer yields a single [`Error::NeedMoreBytes`], after which the
+/// iterator stops. The truncated bytes are left in the buffer (see [`VarintIter::into_inner`]).
+#[cfg(feature = "bytes")]
+pub struct VarintIter<B> {
+    buf: B,
+    failed: bool,
+}
+
+#[cfg(feature = "bytes")]
+impl<B: bytes::Buf> VarintIter<B> {
+    pub fn new(buf: B) -> Self {
+        Self { buf, failed: false }
+    }
+
+    /// Returns the underlying buffer
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<B: bytes::Buf> Iterator for VarintIter<B> {
+    type Item = Result<u64, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || !self.buf.has_remaining() {
+            return None;
+        }
+        let res = try_read_varint(&mut self.buf);
+        self.failed = res.is_err();
+        Some(res)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<B: bytes::Buf> core::iter::FusedIterator for VarintIter<B> {}
+
+/// Returns the largest value that can be encoded in `len` bytes, the inverse of [`encoded_len`].
+///
+/// Each byte holds 7 bits of the value, except for 9 byte varints which hold a full 64 bits.
+/// Panics if `len` is not in `1..=9`.
+pub fn max_value(len: usize) -> u64 {
+    match len {
+        1..=8 => (1 << (7 * len)) - 1,
+        9 => u64::MAX,
+        _ => panic!("varints are between 1 and 9 bytes long, got {len}"),
+    }
 }
 
 /// Encode a varint, returns size of the varint
 pub fn encode_varint(val: u64, buf: &mut [u8]) -> usize {
     let bitlen = u64::BITS - val.leading_zeros();
-    let len = ceil_div(bitlen, 7);
+    let len = bitlen.div_ceil(7);
     match len {
         0..=1 => {
             buf[0] = val as u8;
@@ -115,13 +306,22 @@ pub fn encode_varint(val: u64, buf: &mut [u8]) -> usize {
         },
         9.. => {
             buf[0] = 0xFF;
-            buf[1..].copy_from_slice(&val.to_be_bytes());
+            buf[1..9].copy_from_slice(&val.to_be_bytes());
             9
         },
     }
 }
 
-/// Read a varint from a [`bytes::Buf`], advancing the buffer
+/// Encode a varint, returns size of the varint or [`Error::NeedMoreBytes`] if `buf` is too small
+pub fn try_encode_varint(val: u64, buf: &mut [u8]) -> Result<usize, Error> {
+    let len = encoded_len(val);
+    if buf.len() < len {
+        return Err(Error::NeedMoreBytes { have: buf.len(), need: len });
+    }
+    Ok(encode_varint(val, buf))
+}
+
+/// Write a varint to a [`bytes::BufMut`]
 #[cfg(feature = "bytes")]
 pub fn write_varint(val: u64, dest: &mut impl bytes::BufMut) {
     let mut buf = [0; 9];
@@ -129,12 +329,115 @@ pub fn write_varint(val: u64, dest: &mut impl bytes::BufMut) {
     dest.put_slice(&buf[..size]);
 }
 
+/// Write varints to a [`bytes::BufMut`], encoding them into a stack buffer so `dest` sees one `put_slice` for each
+/// few hundred bytes instead of one per value.
+///
+/// [`bytes::BufMut`] can't reserve space, so reserve growable buffers up front with the total of [`encoded_len`].
+#[cfg(feature = "bytes")]
+pub fn write_varints(vals: &[u64], dest: &mut impl bytes::BufMut) {
+    let mut buf = [0; 256];
+    let mut len = 0;
+    for val in vals {
+        if buf.len() - len < 9 {
+            dest.put_slice(&buf[..len]);
+            len = 0;
+        }
+        len += encode_varint(*val, &mut buf[len..]);
+    }
+    dest.put_slice(&buf[..len]);
+}
+
+/// Copy bytes from the front of a [`bytes::Buf`] into `dest` without advancing it, returns the number copied.
+///
+/// Bytes split across chunks are gathered with [`bytes::Buf::chunks_vectored`], up to 16 chunks.
+#[cfg(feature = "bytes")]
+pub(crate) fn peek_bytes(src: &impl bytes::Buf, dest: &mut [u8]) -> usize {
+    let mut filled = 0;
+    let mut chunks = [std::io::IoSlice::new(&[]); 16];
+    let n = src.chunks_vectored(&mut chunks);
+    for chunk in &chunks[..n] {
+        let take = chunk.len().min(dest.len() - filled);
+        dest[filled..filled + take].copy_from_slice(&chunk[..take]);
+        filled += take;
+    }
+    filled
+}
+
+/// Decode a frame's length prefix without advancing, returns the length and the prefix's length.
+///
+/// Returns [`Error::Fragmented`] if the prefix isn't visible through [`bytes::Buf::chunks_vectored`].
+#[cfg(feature = "bytes")]
+fn frame_len(src: &impl bytes::Buf) -> Result<(u64, usize), Error> {
+    let prefix_len = buffered_varint_len(src)?;
+    let mut buf = [0; 9];
+    if peek_bytes(src, &mut buf[..prefix_len]) < prefix_len {
+        return Err(Error::Fragmented);
+    }
+    Ok((decode_varint_unchecked(&buf[..prefix_len]), prefix_len))
+}
+
+/// Write a length-delimited frame: the length of `payload` as a varint, followed by `payload`
+#[cfg(feature = "bytes")]
+pub fn write_frame(payload: &[u8], dest: &mut impl bytes::BufMut) {
+    write_varint(payload.len() as u64, dest);
+    dest.put_slice(payload);
+}
+
+/// Read a frame written by [`write_frame`], advancing the buffer past it.
+///
+/// Never advances on error. Returns [`Error::NeedMoreBytes`] if the buffer does not hold the complete frame, and
+/// [`Error::Fragmented`] if the length prefix isn't visible through [`bytes::Buf::chunks_vectored`], in which case
+/// copy the buffer into contiguous memory and read from that.
+#[cfg(feature = "bytes")]
+pub fn read_frame(src: &mut impl bytes::Buf) -> Result<bytes::Bytes, Error> {
+    let have = src.remaining();
+    let (len, prefix_len) = frame_len(src)?;
+    let len: usize = len.try_into().map_err(|_| Error::Overflow)?;
+    let need = prefix_len.saturating_add(len);
+    if have < need {
+        return Err(Error::NeedMoreBytes { have, need });
+    }
+    src.advance(prefix_len);
+    Ok(src.copy_to_bytes(len))
+}
+
+/// Read a frame written by [`write_frame`] of at most `max_len` bytes, advancing the buffer past it.
+///
+/// Never advances on error, and returns [`Error::Fragmented`] like [`read_frame`]. Returns [`Error::TooLong`] as soon
+/// as the length prefix is readable, so a hostile prefix is rejected before waiting for its payload, and
+/// [`Error::NeedMoreBytes`] with the whole frame's length if the buffer doesn't hold it yet.
+#[cfg(feature = "bytes")]
+pub fn try_read_frame(src: &mut impl bytes::Buf, max_len: usize) -> Result<bytes::Bytes, Error> {
+    let have = src.remaining();
+    let (len, prefix_len) = frame_len(src)?;
+    if len > max_len as u64 {
+        return Err(Error::TooLong { len, max: max_len });
+    }
+    let need = prefix_len.saturating_add(len as usize);
+    if have < need {
+        return Err(Error::NeedMoreBytes { have, need });
+    }
+    src.advance(prefix_len);
+    Ok(src.copy_to_bytes(len as usize))
+}
+
+/// Encode a `usize` as a varint, returns size of the varint
+pub fn encode_uvarint(val: usize, buf: &mut [u8]) -> usize {
+    encode_varint(val as u64, buf)
+}
+
+/// Decode a varint as a `usize`, returns None if src does not have enough characters or the value does not fit in a
+/// `usize` on this target.
+pub fn decode_uvarint(src: &[u8]) -> Option<usize> {
+    decode_varint(src)?.try_into().ok()
+}
+
 // zigzag encoding is based on the following algorithm:
 // https://gist.github.com/mfuerstenau/ba870a29e16536fdbaba
 
 /// Encode a signed integer with zigzag encoding (more compact than twos complement if negatives are common)
 pub fn zigzag_encode(val: i64) -> u64 {
-    ((val >> i64::BITS-1) ^ (val << 1)) as u64
+    ((val >> (i64::BITS - 1)) ^ (val << 1)) as u64
 }
 
 /// Decode a signed integer with zigzag encoding (more compact than twos complement if negatives are common)
@@ -142,23 +445,177 @@ pub fn zigzag_decode(val: u64) -> i64 {
     ((val >> 1) as i64) ^ -(val as i64 & 1)
 }
 
+/// Append the varint encoding of every value in `vals` to `out`
+pub fn encode_varints(vals: &[u64], out: &mut Vec<u8>) {
+    let total: usize = vals.iter().map(|val| encoded_len(*val)).sum();
+    let mut pos = out.len();
+    out.resize(pos + total, 0);
+    for val in vals {
+        pos += encode_varint(*val, &mut out[pos..]);
+    }
+}
+
+/// Decode exactly `n` varints from the start of `src`, returning the values and the number of bytes consumed.
+pub fn decode_varints(src: &[u8], n: usize) -> Result<(Vec<u64>, usize), Error> {
+    // every varint is at least one byte, so don't trust `n` beyond that
+    let mut vals = Vec::with_capacity(n.min(src.len()));
+    let mut pos = 0;
+    for _ in 0..n {
+        let (val, len) = decode_at(src, pos)?;
+        vals.push(val);
+        pos += len;
+    }
+    Ok((vals, pos))
+}
+
+/// Decode varints from `src` into `out` until `out` is full or `src` is exhausted, returns the number of values
+/// decoded.
+///
+/// This is a faster equivalent of calling [`decode_varint`] in a loop for large arrays. Returns
+/// [`Error::NeedMoreBytes`] if `src` ends partway through a varint.
+pub fn decode_varints_fast(src: &[u8], out: &mut [u64]) -> Result<usize, Error> {
+    let mut pos = 0;
+    let mut count = 0;
+    // while there are at least 9 bytes left, every varint can be read out of a fixed size window
+    while count < out.len() && pos + 9 <= src.len() {
+        let window: &[u8; 9] = src[pos..pos + 9].try_into().unwrap();
+        let len = decode_varint_len(window[0]);
+        out[count] = if len == 9 {
+            u64::from_be_bytes(window[1..].try_into().unwrap())
+        } else {
+            let word = u64::from_be_bytes(window[..8].try_into().unwrap());
+            (word >> (64 - 8 * len)) & ((1 << (7 * len)) - 1)
+        };
+        pos += len;
+        count += 1;
+    }
+    while count < out.len() && pos < src.len() {
+        let (val, len) = decode_at(src, pos)?;
+        out[count] = val;
+        pos += len;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Append `sorted` to `out` as delta-encoded varints: the first value, followed by the zigzag encoded difference
+/// between each value and the one before it.
+///
+/// Differences are computed with wrapping arithmetic, so unsorted input still round-trips exactly through
+/// [`decode_deltas`], it just won't be any smaller than [`encode_varints`].
+pub fn encode_deltas(sorted: &[u64], out: &mut Vec<u8>) {
+    let mut prev = 0u64;
+    let deltas: Vec<u64> = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, val)| {
+            let delta = val.wrapping_sub(prev);
+            prev = *val;
+            if i == 0 { delta } else { zigzag_encode(delta as i64) }
+        })
+        .collect();
+    encode_varints(&deltas, out);
+}
+
+/// Decode `n` values written by [`encode_deltas`], returning the values and the number of bytes consumed.
+pub fn decode_deltas(src: &[u8], n: usize) -> Result<(Vec<u64>, usize), Error> {
+    let (mut vals, consumed) = decode_varints(src, n)?;
+    let mut prev = 0u64;
+    for (i, val) in vals.iter_mut().enumerate() {
+        let delta = if i == 0 { *val } else { zigzag_decode(*val) as u64 };
+        prev = prev.wrapping_add(delta);
+        *val = prev;
+    }
+    Ok((vals, consumed))
+}
+
+/// Append a tagged field to `out`.
+///
+/// The layout is the tag as a varint, immediately followed by the value as a varint:
+///
+/// ```text
+/// [tag varint (1-5 bytes)] [value varint (1-9 bytes)]
+/// ```
+pub fn encode_field(tag: u32, val: u64, out: &mut Vec<u8>) {
+    encode_varints(&[tag as u64, val], out);
+}
+
+/// Decode a field written by [`encode_field`], returning `(tag, val)` and the number of bytes consumed.
+///
+/// Returns [`Error::Overflow`] if the tag does not fit in a `u32`.
+pub fn decode_field(src: &[u8]) -> Result<((u32, u64), usize), Error> {
+    let (vals, consumed) = decode_varints(src, 2)?;
+    let tag = vals[0].try_into().map_err(|_| Error::Overflow)?;
+    Ok(((tag, vals[1]), consumed))
+}
+
+/// Append a list of key-value pairs to `out`: the number of pairs as a varint, followed by each key and value as
+/// varints.
+pub fn encode_pairs(pairs: &[(u64, u64)], out: &mut Vec<u8>) {
+    encode_varints(&[pairs.len() as u64], out);
+    for (key, val) in pairs {
+        encode_varints(&[*key, *val], out);
+    }
+}
+
+/// Decode pairs written by [`encode_pairs`], returning the pairs and the number of bytes consumed.
+///
+/// Returns [`Error::NeedMoreBytes`] without allocating if `src` is too short for the declared number of pairs.
+pub fn decode_pairs(src: &[u8]) -> Result<(Vec<(u64, u64)>, usize), Error> {
+    let (count, mut pos) = try_decode_varint(src)?;
+    // every pair is at least two bytes, so don't trust `count` beyond that
+    let min_len = usize::try_from(count).ok().and_then(|count| count.checked_mul(2)?.checked_add(pos));
+    let count = match min_len {
+        Some(min_len) if min_len <= src.len() => count as usize,
+        _ => return Err(Error::NeedMoreBytes { have: src.len(), need: min_len.unwrap_or(usize::MAX) }),
+    };
+    let mut pairs = Vec::with_capacity(count);
+    for _ in 0..count {
+        let (key, key_len) = decode_at(src, pos)?;
+        pos += key_len;
+        let (val, val_len) = decode_at(src, pos)?;
+        pos += val_len;
+        pairs.push((key, val));
+    }
+    Ok((pairs, pos))
+}
+
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use rand::Rng;
 
     use super::*;
 
+    /// A buffer that keeps the default [`bytes::Buf::chunks_vectored`], only showing its first chunk
+    #[cfg(feature = "bytes")]
+    pub(crate) struct FirstChunk<B>(pub(crate) B);
+
+    #[cfg(feature = "bytes")]
+    impl<B: bytes::Buf> bytes::Buf for FirstChunk<B> {
+        fn remaining(&self) -> usize {
+            self.0.remaining()
+        }
+        fn chunk(&self) -> &[u8] {
+            self.0.chunk()
+        }
+        fn advance(&mut self, cnt: usize) {
+            self.0.advance(cnt)
+        }
+    }
+
+    #[cfg(feature = "bytes")]
     #[test]
     pub fn read_single_byte() {
         for i in 0..127 {
-            assert_eq!(read_varint(&mut &[i][..]), i as u64);
+            assert_eq!(read_varint(&mut &[i][..]), Some(i as u64));
         }
     }
 
+    #[cfg(feature = "bytes")]
     #[test]
     pub fn read_knowns() {
-        assert_eq!(read_varint(&mut &[0xFF; 9][..]), u64::MAX);
-        assert_eq!(read_varint(&mut &[0b1000_0001, 0b1100_1000][..]), 456);
+        assert_eq!(read_varint(&mut &[0xFF; 9][..]), Some(u64::MAX));
+        assert_eq!(read_varint(&mut &[0b1000_0001, 0b1100_1000][..]), Some(456));
     }
 
     #[cfg(feature = "bytes")]
@@ -166,7 +623,7 @@ mod test {
     pub fn read_many() {
         use bytes::{BytesMut, Buf};
         
-        let mut nums: [u64; 10] = rand::random();
+        let nums: [u64; 10] = rand::random();
         let mut buf = BytesMut::new();
         for num in nums {
             write_varint(num, &mut buf);
@@ -174,12 +631,280 @@ mod test {
         let mut buf = buf.freeze();
         let mut decoded = vec![];
         while buf.remaining() > 0 {
-            decoded.push(read_varint(&mut buf));
+            decoded.push(read_varint(&mut buf).unwrap());
         }
         
         assert_eq!(&nums, &decoded[..]);
     }
 
+    #[cfg(feature = "bytes")]
+    #[test]
+    pub fn peek_len() {
+        use bytes::{BytesMut, Buf};
+
+        let mut buf = BytesMut::new();
+        for val in [0, 456, u64::MAX, 1 << 30] {
+            write_varint(val, &mut buf);
+        }
+        let mut buf = buf.freeze();
+        while buf.has_remaining() {
+            let len = peek_varint_len(&buf).unwrap();
+            let before = buf.remaining();
+            read_varint(&mut buf).unwrap();
+            assert_eq!(len, before - buf.remaining());
+        }
+        assert_eq!(peek_varint_len(&buf), None);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    pub fn iter_truncated() {
+        use bytes::{BytesMut, Buf};
+
+        let nums = [1, 456, u64::MAX, 1 << 40];
+        let mut buf = BytesMut::new();
+        for num in nums {
+            write_varint(num, &mut buf);
+        }
+        write_varint(1 << 20, &mut buf);
+        buf.truncate(buf.len() - 1);
+
+        let mut iter = VarintIter::new(buf.freeze());
+        for num in nums {
+            assert_eq!(iter.next(), Some(Ok(num)));
+        }
+        assert_eq!(iter.next(), Some(Err(Error::NeedMoreBytes { have: 2, need: 3 })));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.into_inner().remaining(), 2);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    pub fn read_many_into() {
+        use bytes::Buf;
+
+        let vals = [1, 300, u64::MAX, 1 << 40, 7];
+        let mut buf = vec![];
+        encode_varints(&vals, &mut buf);
+        let (front, back) = buf.split_at(5);
+
+        let mut out = [0; 5];
+        let mut short = &buf[..buf.len() - 1];
+        assert_eq!(read_varints_into(&mut short, &mut out), Err(Error::NeedMoreBytes { have: 18, need: 19 }));
+        assert_eq!(short.len(), buf.len() - 1);
+        let mut split_short = front.chain(&back[..back.len() - 1]);
+        assert_eq!(read_varints_into(&mut split_short, &mut out), Err(Error::NeedMoreBytes { have: 18, need: 19 }));
+        assert_eq!(split_short.remaining(), buf.len() - 1);
+
+        let mut src = front.chain(back).chain(&[0x2A][..]);
+        read_varints_into(&mut src, &mut out).unwrap();
+        assert_eq!(out, vals);
+        assert_eq!(src.remaining(), 1);
+        let mut last = [0];
+        read_varints_into(&mut src, &mut last).unwrap();
+        assert_eq!(last, [0x2A]);
+        assert_eq!(read_varints_into(&mut src, &mut []), Ok(()));
+
+        // only shows its first chunk, so the second half is read a varint at a time
+        let mut hidden = FirstChunk(front.chain(back));
+        read_varints_into(&mut hidden, &mut out).unwrap();
+        assert_eq!(out, vals);
+        let mut hidden_short = FirstChunk(front.chain(&back[..back.len() - 1]));
+        assert_eq!(read_varints_into(&mut hidden_short, &mut out), Err(Error::NeedMoreBytes { have: 0, need: 1 }));
+        // advanced past the four read before running out
+        assert_eq!(hidden_short.remaining(), 0);
+        assert_eq!(out[..4], vals[..4]);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    pub fn skip() {
+        use bytes::Buf;
+
+        let mut buf = &[0b1000_0001, 0b1100_1000, 42][..];
+        assert_eq!(skip_varint(&mut buf), Ok(2));
+        assert_eq!(read_varint(&mut buf), Some(42));
+        assert_eq!(skip_varint(&mut buf), Err(Error::NeedMoreBytes { have: 0, need: 1 }));
+
+        let mut buf = &[0xFF, 0, 0][..];
+        assert_eq!(skip_varint(&mut buf), Err(Error::NeedMoreBytes { have: 3, need: 9 }));
+        assert_eq!(buf.remaining(), 3);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    pub fn write_many() {
+        let mut rng = rand::thread_rng();
+        let vals: Vec<u64> = (0..1000).map(|_| rng.gen::<u64>() >> rng.gen_range(0..64)).collect();
+        let mut buf = bytes::BytesMut::new();
+        write_varints(&vals, &mut buf);
+
+        let mut expected = vec![];
+        encode_varints(&vals, &mut expected);
+        assert_eq!(buf[..], expected[..]);
+        let decoded: Result<Vec<u64>, _> = VarintIter::new(buf.freeze()).collect();
+        assert_eq!(decoded, Ok(vals));
+
+        let mut empty = vec![];
+        write_varints(&[], &mut empty);
+        assert!(empty.is_empty());
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    pub fn frames_roundtrip() {
+        use bytes::{BytesMut, Buf};
+
+        let payloads: Vec<Vec<u8>> = [0, 1, 127, 128, 5000, 20000].iter().map(|len| vec![*len as u8; *len]).collect();
+        let mut buf = BytesMut::new();
+        for payload in &payloads {
+            write_frame(payload, &mut buf);
+        }
+        let mut buf = buf.freeze();
+        for payload in &payloads {
+            assert_eq!(read_frame(&mut buf).unwrap(), &payload[..]);
+        }
+        assert!(!buf.has_remaining());
+        assert_eq!(read_frame(&mut buf), Err(Error::NeedMoreBytes { have: 0, need: 1 }));
+    }
+
+    #[test]
+    pub fn try_decode() {
+        assert_eq!(try_decode_varint(&[0x7F, 0xAA]), Ok((0x7F, 1)));
+        assert_eq!(try_decode_varint(&[]), Err(Error::NeedMoreBytes { have: 0, need: 1 }));
+        assert_eq!(try_decode_varint(&[0xFF, 0, 0]), Err(Error::NeedMoreBytes { have: 3, need: 9 }));
+        // overlong, like `decode_varint`
+        assert_eq!(try_decode_varint(&[0b1000_0000, 5]), Ok((5, 2)));
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    pub fn try_frames() {
+        use bytes::Buf;
+
+        let mut buf = bytes::BytesMut::new();
+        write_frame(b"hello", &mut buf);
+        write_frame(&[0; 300], &mut buf);
+        let mut buf = buf.freeze();
+
+        let mut truncated = buf.slice(..4);
+        assert_eq!(try_read_frame(&mut truncated, 10), Err(Error::NeedMoreBytes { have: 4, need: 6 }));
+        assert_eq!(truncated.len(), 4);
+
+        assert_eq!(try_read_frame(&mut buf, 10).unwrap(), &b"hello"[..]);
+        let mut partial = buf.slice(..1);
+        assert_eq!(try_read_frame(&mut partial, 10), Err(Error::NeedMoreBytes { have: 1, need: 2 }));
+        assert_eq!(try_read_frame(&mut buf.slice(..2), 10), Err(Error::TooLong { len: 300, max: 10 }));
+        assert_eq!(try_read_frame(&mut buf, 10), Err(Error::TooLong { len: 300, max: 10 }));
+        assert_eq!(try_read_frame(&mut buf, 300).unwrap(), &[0; 300][..]);
+        assert!(buf.is_empty());
+
+        let mut buf = vec![];
+        write_frame(&[7; 300], &mut buf);
+        let (front, back) = buf.split_at(1);
+        let mut hidden = FirstChunk(front.chain(back));
+        assert_eq!(try_read_frame(&mut hidden, 300), Err(Error::Fragmented));
+        assert_eq!(try_read_frame(&mut hidden, 10), Err(Error::Fragmented));
+        assert_eq!(hidden.remaining(), 302);
+    }
+
+    #[cfg(all(feature = "bytes", target_pointer_width = "64"))]
+    #[test]
+    pub fn try_frame_unbounded() {
+        // with no effective limit, the length needed used to overflow when adding the prefix
+        let mut src = &[0xFF; 9][..];
+        assert_eq!(try_read_frame(&mut src, usize::MAX), Err(Error::NeedMoreBytes { have: 9, need: usize::MAX }));
+        assert_eq!(src.len(), 9);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    pub fn frame_truncated() {
+        use bytes::{BytesMut, Buf};
+
+        let mut buf = BytesMut::new();
+        write_frame(&[7; 100], &mut buf);
+        buf.truncate(41);
+        let mut buf = buf.freeze();
+        assert_eq!(read_frame(&mut buf), Err(Error::NeedMoreBytes { have: 41, need: 101 }));
+        assert_eq!(buf.remaining(), 41);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    pub fn frame_hidden_prefix() {
+        use bytes::Buf;
+
+        let mut buf = vec![];
+        write_frame(&[7; 300], &mut buf);
+        let (front, back) = buf.split_at(1);
+        let mut hidden = FirstChunk(front.chain(back));
+        assert_eq!(read_frame(&mut hidden), Err(Error::Fragmented));
+        assert_eq!(hidden.remaining(), 302);
+        let mut hidden_short = FirstChunk(front.chain(&back[..back.len() - 10]));
+        assert_eq!(read_frame(&mut hidden_short), Err(Error::Fragmented));
+        assert_eq!(hidden_short.remaining(), 292);
+
+        let mut contiguous = hidden.copy_to_bytes(hidden.remaining());
+        assert_eq!(read_frame(&mut contiguous).unwrap(), &[7; 300][..]);
+    }
+
+    #[test]
+    pub fn progress() {
+        let mut buf = [0; 9];
+        let len = encode_varint(1 << 20, &mut buf);
+        assert_eq!(len, 3);
+        assert_eq!(decode_varint_progress(&[]), Err(1));
+        assert_eq!(decode_varint_progress(&buf[..1]), Err(3));
+        assert_eq!(decode_varint_progress(&buf[..2]), Err(3));
+        assert_eq!(decode_varint_progress(&buf), Ok((1 << 20, 3)));
+    }
+
+    #[test]
+    pub fn canonical() {
+        assert_eq!(decode_varint_canonical(&[5]), Ok(5));
+        assert_eq!(decode_varint_canonical(&[0b1000_0001, 0b1100_1000]), Ok(456));
+        assert_eq!(decode_varint_canonical(&[0xFF; 9]), Ok(u64::MAX));
+        assert_eq!(decode_varint_canonical(&[0b1000_0000]), Err(Error::NeedMoreBytes { have: 1, need: 2 }));
+
+        let overlong = [0xFF, 0, 0, 0, 0, 0, 0, 0, 5];
+        assert_eq!(decode_varint(&overlong), Some(5));
+        assert_eq!(decode_varint_canonical(&overlong), Err(Error::NonCanonical));
+        assert_eq!(decode_varint(&[0b1000_0000, 5]), Some(5));
+        assert_eq!(decode_varint_canonical(&[0b1000_0000, 5]), Err(Error::NonCanonical));
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    pub fn read_across_chunks() {
+        use bytes::Buf;
+
+        let mut buf = (&[0b1000_0001][..]).chain(&[0b1100_1000][..]);
+        assert_eq!(read_varint(&mut buf), Some(456));
+        assert!(!buf.has_remaining());
+
+        let mut buf = (&[0b1000_0001][..]).chain(&[0b1100_1000][..]);
+        assert_eq!(try_read_varint(&mut buf), Ok(456));
+
+        let mut buf = (&[0xFF, 0xFF, 0xFF][..]).chain(&[0xFF; 6][..]).chain(&[7][..]);
+        assert_eq!(read_varint(&mut buf), Some(u64::MAX));
+        assert_eq!(read_varint(&mut buf), Some(7));
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    pub fn read_incomplete() {
+        use bytes::Buf;
+
+        assert_eq!(read_varint(&mut &[][..]), None);
+        let mut buf = &[0b1000_0001][..];
+        assert_eq!(read_varint(&mut buf), None);
+        assert_eq!(buf.len(), 1);
+        let mut buf = (&[0xFF, 0xFF][..]).chain(&[0xFF; 3][..]);
+        assert_eq!(read_varint(&mut buf), None);
+        assert_eq!(buf.remaining(), 5);
+    }
+
     #[test]
     pub fn encode_knowns() {
         let mut buf = [0; 9];
@@ -189,11 +914,77 @@ mod test {
         assert_eq!(&buf[..len], [0xFF; 9]);
     }
 
+    #[test]
+    pub fn decode_every_length() {
+        // the scratch buffer used to be 8 bytes, so every length panicked
+        for len in 1..=9 {
+            let mut src = [0xFF; 9];
+            if len < 9 {
+                src[0] ^= 0x80 >> (len - 1);
+            }
+            let expected = if len < 9 { (1 << (7 * len)) - 1 } else { u64::MAX };
+            assert_eq!(decode_varint_unchecked(&src[..len]), expected, "{len}");
+        }
+    }
+
+    #[test]
+    pub fn encode_into_long_buffer() {
+        // 9 byte varints were written with buf[1..], which panicked unless buf was exactly 9 bytes
+        let mut buf = [0xAB; 16];
+        assert_eq!(encode_varint(u64::MAX, &mut buf), 9);
+        assert_eq!(buf[..9], [0xFF; 9]);
+        assert_eq!(buf[9..], [0xAB; 7]);
+    }
+
+    #[test]
+    pub fn try_encode_small_buffers() {
+        let mut buf = [0; 9];
+        for needed in 1..=9 {
+            let val = if needed == 9 { u64::MAX } else { (1u64 << (7 * needed)) - 1 };
+            for len in 1..needed {
+                assert_eq!(try_encode_varint(val, &mut buf[..len]), Err(Error::NeedMoreBytes { have: len, need: needed }));
+            }
+            assert_eq!(try_encode_varint(val, &mut buf[..needed]), Ok(needed));
+            assert_eq!(decode_varint(&buf[..needed]), Some(val));
+        }
+        assert_eq!(try_encode_varint(0, &mut []), Err(Error::NeedMoreBytes { have: 0, need: 1 }));
+    }
+
+    #[test]
+    pub fn uvarint_roundtrip() {
+        let mut buf = [0; 9];
+        for val in [0, 1, 456, u32::MAX as usize, usize::MAX] {
+            let len = encode_uvarint(val, &mut buf);
+            assert_eq!(decode_uvarint(&buf[..len]), Some(val));
+        }
+        assert_eq!(decode_uvarint(&[]), None);
+    }
+
+    #[cfg(target_pointer_width = "32")]
+    #[test]
+    pub fn uvarint_overflow() {
+        let mut buf = [0; 9];
+        let len = encode_varint(u32::MAX as u64 + 1, &mut buf);
+        assert_eq!(decode_uvarint(&buf[..len]), None);
+    }
+
+    #[test]
+    pub fn max_values() {
+        assert_eq!(max_value(1), 127);
+        assert_eq!(max_value(2), 16383);
+        for len in 1..=9 {
+            assert_eq!(encoded_len(max_value(len)), len);
+            if len < 9 {
+                assert_eq!(encoded_len(max_value(len) + 1), len + 1);
+            }
+        }
+    }
+
     fn test_roundtrip(val: u64) -> usize {
         let mut buf = [0; 9];
         let len = encode_varint(val, &mut buf);
-        let decoded = read_varint(&mut &buf[..len]);
-        assert_eq!(val, decoded);
+        let decoded = decode_varint(&buf[..len]);
+        assert_eq!(Some(val), decoded);
         len
     }
 
@@ -214,6 +1005,150 @@ mod test {
         }
     }
 
+    #[test]
+    pub fn encoded_len_matches_encode() {
+        let mut rng = rand::thread_rng();
+        let mut buf = [0; 9];
+
+        for shift in 0..64 {
+            let val: u64 = rng.gen::<u64>() >> shift;
+            assert_eq!(encoded_len(val), encode_varint(val, &mut buf));
+        }
+        assert_eq!(encoded_len(0), 1);
+        assert_eq!(encoded_len(u64::MAX), 9);
+    }
+
+    #[test]
+    pub fn batch_roundtrip() {
+        let mut rng = rand::thread_rng();
+        let vals: Vec<u64> = (0..1000).map(|_| rng.gen::<u64>() >> rng.gen_range(0..64)).collect();
+
+        let mut out = vec![0xAB];
+        encode_varints(&vals, &mut out);
+        let (decoded, consumed) = decode_varints(&out[1..], vals.len()).unwrap();
+        assert_eq!(decoded, vals);
+        assert_eq!(consumed, out.len() - 1);
+
+        assert_eq!(
+            decode_varints(&out[1..consumed], vals.len()),
+            Err(Error::NeedMoreBytes { have: consumed - 1, need: consumed })
+        );
+        assert_eq!(decode_varints(&[], 0), Ok((vec![], 0)));
+    }
+
+    #[test]
+    pub fn fast_matches_naive() {
+        let mut rng = rand::thread_rng();
+        let mut vals: Vec<u64> = (0..10_000).map(|_| rng.gen::<u64>() >> rng.gen_range(0..64)).collect();
+        // end on a multi-byte varint so truncating the last byte leaves a partial varint
+        vals.push(u64::MAX);
+        let mut src = vec![];
+        encode_varints(&vals, &mut src);
+
+        let mut out = vec![0; vals.len()];
+        assert_eq!(decode_varints_fast(&src, &mut out), Ok(vals.len()));
+        let mut naive = vec![];
+        let mut pos = 0;
+        while let Some(val) = decode_varint(&src[pos..]) {
+            naive.push(val);
+            pos += decode_varint_len(src[pos]);
+        }
+        assert_eq!(out, naive);
+
+        let mut out = vec![0; 10];
+        assert_eq!(decode_varints_fast(&src, &mut out), Ok(10));
+        assert_eq!(out, vals[..10]);
+
+        let mut out = vec![0; vals.len() + 1];
+        assert_eq!(decode_varints_fast(&src, &mut out), Ok(vals.len()));
+        assert_eq!(
+            decode_varints_fast(&src[..src.len() - 1], &mut out),
+            Err(Error::NeedMoreBytes { have: src.len() - 1, need: src.len() })
+        );
+    }
+
+    #[test]
+    pub fn deltas_roundtrip() {
+        let mut rng = rand::thread_rng();
+        let mut vals: Vec<u64> = (0..1000).map(|_| rng.gen_range(1_000_000_000..1_000_100_000)).collect();
+        vals.sort_unstable();
+
+        let mut deltas = vec![];
+        encode_deltas(&vals, &mut deltas);
+        assert_eq!(decode_deltas(&deltas, vals.len()), Ok((vals.clone(), deltas.len())));
+
+        let mut naive = vec![];
+        encode_varints(&vals, &mut naive);
+        assert!(deltas.len() < naive.len(), "{} >= {}", deltas.len(), naive.len());
+    }
+
+    #[test]
+    pub fn deltas_edge_cases() {
+        for vals in [vec![], vec![0, u64::MAX], vec![u64::MAX, 0, 5, 3], vec![7, 7, 7]] {
+            let mut out = vec![];
+            encode_deltas(&vals, &mut out);
+            assert_eq!(decode_deltas(&out, vals.len()), Ok((vals, out.len())));
+        }
+    }
+
+    #[test]
+    pub fn field_roundtrip() {
+        let mut rng = rand::thread_rng();
+        let pairs: Vec<(u32, u64)> = (0..1000)
+            .map(|_| (rng.gen::<u32>() >> rng.gen_range(0..32), rng.gen::<u64>() >> rng.gen_range(0..64)))
+            .collect();
+
+        let mut out = vec![];
+        for (tag, val) in &pairs {
+            encode_field(*tag, *val, &mut out);
+        }
+        let mut src = &out[..];
+        for pair in pairs {
+            let (decoded, consumed) = decode_field(src).unwrap();
+            assert_eq!(decoded, pair);
+            src = &src[consumed..];
+        }
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    pub fn field_errors() {
+        let mut out = vec![];
+        encode_varints(&[u32::MAX as u64 + 1, 5], &mut out);
+        assert_eq!(decode_field(&out), Err(Error::Overflow));
+        assert_eq!(decode_field(&[3]), Err(Error::NeedMoreBytes { have: 1, need: 2 }));
+    }
+
+    #[test]
+    pub fn pairs_roundtrip() {
+        let mut rng = rand::thread_rng();
+        for n in [0, 1, 2, 100] {
+            let pairs: Vec<(u64, u64)> = (0..n)
+                .map(|_| (rng.gen::<u64>() >> rng.gen_range(0..64), rng.gen::<u64>() >> rng.gen_range(0..64)))
+                .collect();
+            let mut buf = vec![];
+            encode_pairs(&pairs, &mut buf);
+            let len = buf.len();
+            buf.push(0xAB);
+            assert_eq!(decode_pairs(&buf), Ok((pairs, len)));
+        }
+        assert_eq!(decode_pairs(&[]), Err(Error::NeedMoreBytes { have: 0, need: 1 }));
+    }
+
+    #[test]
+    pub fn pairs_hostile_count() {
+        // claims u64::MAX pairs, then holds one
+        let mut buf = vec![];
+        encode_varints(&[u64::MAX, 1, 2], &mut buf);
+        assert_eq!(decode_pairs(&buf), Err(Error::NeedMoreBytes { have: 11, need: usize::MAX }));
+
+        // claims two pairs, holds one and a half
+        let mut buf = vec![];
+        encode_varints(&[2, 1, 2], &mut buf);
+        buf.extend_from_slice(&[0xFF, 0]);
+        assert_eq!(decode_pairs(&buf), Err(Error::NeedMoreBytes { have: 5, need: 12 }));
+    }
+
     #[test]
     pub fn zigzag_encode_known() {
         assert_eq!(0, zigzag_encode(0));
@@ -1,49 +1,319 @@
-use std::fmt::Debug;
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::fmt::{self, Debug};
+use core::ops::{BitAnd, Shl};
+
+use crate::varint::{decode_varint_progress, encode_varints};
+#[cfg(feature = "bytes")]
+use crate::varint::{encoded_len, write_varint};
 
 /// A fixed-length bitmap window, useful for eliminating duplicates in a best-effort stream
+///
+/// Equality is structural: two windows are equal if they have the same base index and bitmap. Windows that have slid
+/// differently may record the same indices but compare unequal, use [`Window::eq_logical`] to compare just the
+/// recorded indices. The rejection counters are not compared.
+#[derive(Clone)]
 pub struct Window<const N: usize = 3> {
     map: [usize; N],
     first_index: u64,
+    duplicates: u64,
+    too_old: u64,
+}
+
+impl<const N: usize> PartialEq for Window<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map && self.first_index == other.first_index
+    }
+}
+
+impl<const N: usize> Eq for Window<N> {}
+
+/// Errors returned by [`Window::from_bytes`] and [`Window::validate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowDecodeError {
+    /// The source was shorter or longer than the encoded window
+    InvalidLength,
+    /// The source encodes a window with a different number of words
+    WrongSize { expected: usize, found: u64 },
+    /// The source sets a bit for an index past `u64::MAX`
+    PastEnd,
+}
+
+impl fmt::Display for WindowDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WindowDecodeError::InvalidLength => f.write_str("invalid encoded window length"),
+            WindowDecodeError::WrongSize { expected, found } => {
+                write!(f, "expected a window of {expected} words, found {found}")
+            }
+            WindowDecodeError::PastEnd => f.write_str("encoded window has indices past u64::MAX"),
+        }
+    }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for WindowDecodeError {}
+
 pub struct Iter<'a, const N: usize> {
     window: &'a Window<N>,
-    idx: u64
+    // offsets from `first_index` of the remaining range, `front..back`
+    front: usize,
+    back: usize,
+}
+
+impl<const N: usize> Iter<'_, N> {
+    fn is_set(&self, adj: usize) -> bool {
+        let word = self.window.map[adj / usize::BITS as usize];
+        word & (1usize << (adj % usize::BITS as usize)) != 0
+    }
 }
 
 impl<const N: usize> Iterator for Iter<'_, N> {
     type Item = u64;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let mut adj: usize = self.idx.saturating_sub(self.window.first_index).try_into().ok()?;
-        loop {
-            if adj >= Window::<N>::LEN {
-                return None;
-            }
-            let word_idx = adj / usize::BITS as usize;
-            let word_offset = adj % usize::BITS as usize;
-            let mask = 1usize << word_offset;
-            let word = self.window.map[word_idx];
-            let val = (word & mask) != 0;
-            if val {
-                let idx = self.window.first_index + adj as u64;
-                self.idx = idx + 1;
+        while self.front < self.back {
+            let adj = self.front;
+            self.front += 1;
+            if self.is_set(adj) {
+                return Some(self.window.first_index + adj as u64);
+            }
+        }
+        None
+    }
+}
+
+impl<const N: usize> DoubleEndedIterator for Iter<'_, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while self.front < self.back {
+            self.back -= 1;
+            if self.is_set(self.back) {
+                return Some(self.window.first_index + self.back as u64);
+            }
+        }
+        None
+    }
+}
+
+impl<const N: usize> core::iter::FusedIterator for Iter<'_, N> {}
+
+/// A word of a window's bitmap
+trait Word: Copy + Eq + BitAnd<Output = Self> + Shl<u32, Output = Self> {
+    const BITS: u32;
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn write_le(self, out: &mut Vec<u8>);
+    fn read_le(src: &[u8]) -> Self;
+}
+
+impl Word for usize {
+    const BITS: u32 = usize::BITS;
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
+
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+    fn read_le(src: &[u8]) -> Self {
+        Self::from_le_bytes(src.try_into().unwrap())
+    }
+}
+
+impl Word for u64 {
+    const BITS: u32 = u64::BITS;
+    const ZERO: Self = 0;
+    const ONE: Self = 1;
+
+    fn write_le(self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+    fn read_le(src: &[u8]) -> Self {
+        Self::from_le_bytes(src.try_into().unwrap())
+    }
+}
+
+/// serialize a window's bitmap: `first_index` and the number of words as varints, followed by each word in little
+/// endian
+fn encode_map<W: Word>(map: &[W], first_index: u64) -> Vec<u8> {
+    let word_size = W::BITS as usize / 8;
+    let mut out = Vec::with_capacity(18 + map.len() * word_size);
+    encode_varints(&[first_index, map.len() as u64], &mut out);
+    for word in map {
+        word.write_le(&mut out);
+    }
+    out
+}
+
+/// rebuild a bitmap serialized by [`encode_map`], rejecting any that sets an index past `u64::MAX`
+fn decode_map<W: Word, const N: usize>(src: &[u8]) -> Result<([W; N], u64), WindowDecodeError> {
+    let (first_index, len) = decode_varint_progress(src).map_err(|_| WindowDecodeError::InvalidLength)?;
+    let src = &src[len..];
+    let (words, len) = decode_varint_progress(src).map_err(|_| WindowDecodeError::InvalidLength)?;
+    if words != N as u64 {
+        return Err(WindowDecodeError::WrongSize { expected: N, found: words });
+    }
+    let src = &src[len..];
+    let word_size = W::BITS as usize / 8;
+    if src.len() != N * word_size {
+        return Err(WindowDecodeError::InvalidLength);
+    }
+    let mut map = [W::ZERO; N];
+    for (word, bytes) in map.iter_mut().zip(src.chunks_exact(word_size)) {
+        *word = W::read_le(bytes);
+    }
+    if past_end(&map, first_index) {
+        return Err(WindowDecodeError::PastEnd);
+    }
+    Ok((map, first_index))
+}
+
+/// returns true if `map` sets a bit for an index past `u64::MAX`
+fn past_end<W: Word>(map: &[W], first_index: u64) -> bool {
+    let Ok(last) = usize::try_from(u64::MAX - first_index) else { return false };
+    (last.saturating_add(1)..map.len() * W::BITS as usize)
+        .any(|adj| map[adj / W::BITS as usize] & (W::ONE << (adj % W::BITS as usize) as u32) != W::ZERO)
+}
+
+/// returns the lowest index at or above `from` that is set in `map`
+fn next_set<W: Word>(map: &[W], first_index: u64, from: u64) -> Option<u64> {
+    let mut adj: usize = from.saturating_sub(first_index).try_into().ok()?;
+    loop {
+        if adj >= map.len() * W::BITS as usize {
+            return None;
+        }
+        let word_idx = adj / W::BITS as usize;
+        let word_offset = (adj % W::BITS as usize) as u32;
+        let mask = W::ONE << word_offset;
+        let word = map[word_idx];
+        let val = (word & mask) != W::ZERO;
+        if val {
+            return Some(first_index + adj as u64);
+        }
+        adj += 1;
+    }
+}
+
+/// Slides `map` forward so that `word_idx`, which is past the end of the map, fits. Returns the new word index.
+///
+/// Beyond the gap, half of the map is dropped to make room for more indices.
+fn slide<W: Word>(map: &mut [W], first_index: &mut u64, word_idx: usize) -> usize {
+    let len = map.len();
+    let gap = word_idx - len;
+    let half = (len / 2).max(1);
+    let shift = gap + half;
+    let keep = len.saturating_sub(shift);
+    map.copy_within(len - keep.., 0);
+    map[keep..].fill(W::ZERO);
+    *first_index += shift as u64 * W::BITS as u64;
+    #[cfg(feature = "tracing")]
+    tracing::trace!(first_index = *first_index, evicted_words = len - keep, "window slid");
+    len - half
+}
+
+/// How a window's gaps look to [`Window::congestion_hint`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CongestionHint {
+    /// Nothing is missing below the highest index seen
+    Healthy,
+    /// A few indices are missing, each on its own or in short runs, as if they were delivered out of order
+    Reordering,
+    /// Runs of indices are missing, or too many are missing overall, as if they were dropped
+    Loss,
+}
+
+/// The thresholds [`Window::congestion_hint_with`] classifies gaps by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CongestionThresholds {
+    /// a run of at least this many consecutive missing indices is loss. Defaults to 3, like TCP's three duplicate acks
+    pub loss_run: u64,
+    /// more than this fraction of the indices up to the highest seen missing is loss. Defaults to 0.1
+    pub loss_ratio: f32,
+}
+
+impl Default for CongestionThresholds {
+    fn default() -> Self {
+        Self { loss_run: 3, loss_ratio: 0.1 }
+    }
+}
+
+/// Write inclusive `(start, end)` ranges to `dest` as pairs of varints, stopping before the first range that would
+/// take the total past `max_bytes` (or past what `dest` has room for). Returns how many ranges were written.
+///
+/// Ranges are written whole or not at all.
+#[cfg(feature = "bytes")]
+pub fn write_ranges(ranges: &[(u64, u64)], dest: &mut impl bytes::BufMut, max_bytes: usize) -> usize {
+    let mut budget = max_bytes.min(dest.remaining_mut());
+    let mut written = 0;
+    for (start, end) in ranges {
+        let len = encoded_len(*start) + encoded_len(*end);
+        if len > budget {
+            break;
+        }
+        write_varint(*start, dest);
+        write_varint(*end, dest);
+        budget -= len;
+        written += 1;
+    }
+    written
+}
+
+/// Iterator over the indices in a window that have not been inserted, see [`Window::gaps`]
+pub struct GapIter<'a, const N: usize> {
+    window: &'a Window<N>,
+    idx: Option<u64>,
+    last: u64,
+}
+
+impl<const N: usize> Iterator for GapIter<'_, N> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(idx) = self.idx.filter(|idx| *idx <= self.last) {
+            self.idx = idx.checked_add(1);
+            if !self.window.contains(idx) {
                 return Some(idx);
             }
-            adj += 1;
         }
+        None
     }
 }
 
 impl<const N: usize> Window<N> {
     const LEN: usize = N * usize::BITS as usize;
+    const NOT_EMPTY: () = assert!(N > 0, "a window needs at least one word");
 
     /// create a new, empty window
     pub fn new() -> Self {
-        Self {
-            map: [0; N],
-            first_index: 0,
-        }
+        Self::from_raw([0; N], 0)
+    }
+
+    /// Create a window from a raw bitmap.
+    ///
+    /// The bitmap is word-major and least significant bit first: index `first_index + i` is bit `i % usize::BITS` of
+    /// `map[i / usize::BITS]`.
+    pub fn from_raw(map: [usize; N], first_index: u64) -> Self {
+        let () = Self::NOT_EMPTY;
+        Self { map, first_index, duplicates: 0, too_old: 0 }
+    }
+
+    /// returns the raw bitmap and the first index, see [`Window::from_raw`] for the layout
+    pub fn raw(&self) -> (&[usize; N], u64) {
+        (&self.map, self.first_index)
+    }
+
+    /// returns the lowest index the window can hold, lower indices can't be inserted
+    pub fn first_index(&self) -> u64 {
+        self.first_index
+    }
+
+    /// returns the highest index the window can hold without sliding, which is at most `u64::MAX`
+    pub fn last_index(&self) -> u64 {
+        self.first_index.saturating_add(Self::LEN as u64 - 1)
+    }
+
+    /// returns the number of indices the window spans
+    pub fn capacity(&self) -> usize {
+        Self::LEN
     }
 
     /// returns true if index can be inserted
@@ -59,46 +329,383 @@ impl<const N: usize> Window<N> {
         self.map[word_idx] & mask == 0
     }
 
+    /// returns true if index has been inserted and is still within the window
+    pub fn contains(&self, index: u64) -> bool {
+        let adjusted_index = match index.checked_sub(self.first_index) {
+            Some(offset) if offset < Self::LEN as u64 => offset as usize,
+            _ => return false
+        };
+        let word_idx = adjusted_index / usize::BITS as usize;
+        let word_offset = adjusted_index as u32 % usize::BITS;
+        self.map[word_idx] & (1usize << word_offset) != 0
+    }
+
     /// Attemps to insert `index`. 
     /// 
     /// If the index has been inserted before, the insert will return false.
     /// The window may return false when given a lower index than one it has seen before, even if the smaller index has 
     /// not yet been seen.
     pub fn insert(&mut self, index: u64) -> bool {
-        let adjusted_index = match index.checked_sub(self.first_index) {
-            Some(offset) => offset,
-            None => return false
-        };
+        match self.set(index) {
+            Some(true) => true,
+            Some(false) => {
+                self.duplicates += 1;
+                false
+            }
+            None => {
+                self.too_old += 1;
+                false
+            }
+        }
+    }
+
+    /// Records `index` without touching the rejection counters. Returns whether it was new, or None if it's below the
+    /// window.
+    fn set(&mut self, index: u64) -> Option<bool> {
+        let adjusted_index = index.checked_sub(self.first_index)?;
         let mut word_idx = adjusted_index as usize / usize::BITS as usize;
         let word_offset = adjusted_index as u32 % usize::BITS;
-        if let Some(gap) = word_idx.checked_sub(N) {
-            let keep = (N / 2 + 1).saturating_sub(gap);
-            self.map.copy_within(N - keep.., 0);
-            self.map[keep..].fill(0);
-            word_idx = N / 2 + 1;
-            self.first_index += (gap + N / 2) as u64 * usize::BITS as u64;
+        if word_idx >= N {
+            word_idx = slide(&mut self.map, &mut self.first_index, word_idx);
         }
 
         let word = &mut self.map[word_idx];
         let mask = 1usize << word_offset;
         let new = (*word & mask) == 0;
         *word |= mask;
-        return new;
+        Some(new)
+    }
+
+    /// returns true if inserting `index` would slide the window forward, evicting older indices
+    pub fn would_slide(&self, index: u64) -> bool {
+        match index.checked_sub(self.first_index) {
+            Some(offset) => offset / usize::BITS as u64 >= N as u64,
+            None => false,
+        }
+    }
+
+    /// Returns how many indices passed to [`Window::insert`], [`Window::insert_range`] and [`Window::insert_sorted`]
+    /// were rejected because they had already been inserted.
+    ///
+    /// [`Window::merge`] isn't counted, and [`Window::reset`] clears the count.
+    pub fn duplicates_rejected(&self) -> u64 {
+        self.duplicates
+    }
+
+    /// returns how many indices were rejected because they were below the window, counted like
+    /// [`Window::duplicates_rejected`]
+    pub fn too_old_rejected(&self) -> u64 {
+        self.too_old
+    }
+
+    /// Inserts every index from `start` to `end` inclusive, a word at a time. Returns how many were newly recorded.
+    ///
+    /// This behaves the same as inserting each index in ascending order, including sliding the window.
+    pub fn insert_range(&mut self, start: u64, end: u64) -> u64 {
+        if start <= end && start < self.first_index {
+            self.too_old += end.min(self.first_index - 1) - start + 1;
+        }
+        let mut new = 0;
+        let mut cur = start.max(self.first_index);
+        while cur <= end {
+            let adjusted_index = cur - self.first_index;
+            let mut word_idx = adjusted_index as usize / usize::BITS as usize;
+            let word_offset = adjusted_index as u32 % usize::BITS;
+            if word_idx >= N {
+                word_idx = slide(&mut self.map, &mut self.first_index, word_idx);
+            }
+
+            let span = (end - cur).min((usize::BITS - 1 - word_offset) as u64) as u32 + 1;
+            let mask = if span == usize::BITS { usize::MAX } else { ((1usize << span) - 1) << word_offset };
+            let word = &mut self.map[word_idx];
+            let newly = (mask & !*word).count_ones() as u64;
+            new += newly;
+            self.duplicates += span as u64 - newly;
+            *word |= mask;
+            cur = match cur.checked_add(span as u64) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        new
+    }
+
+    /// Inserts each index in order, returns how many were newly recorded.
+    ///
+    /// Indices should be in ascending order, which keeps slides to a minimum and avoids dropping lower indices that
+    /// come after a higher one.
+    pub fn insert_sorted(&mut self, iter: impl IntoIterator<Item = u64>) -> usize {
+        iter.into_iter().filter(|idx| self.insert(*idx)).count()
+    }
+
+    /// Forgets `index`, allowing it to be inserted again.
+    ///
+    /// Returns true if the index was in the window and had been inserted. Indices outside of the window are ignored.
+    pub fn unset(&mut self, index: u64) -> bool {
+        let adjusted_index = match index.checked_sub(self.first_index) {
+            Some(offset) if offset < Self::LEN as u64 => offset as usize,
+            _ => return false
+        };
+        let word = &mut self.map[adjusted_index / usize::BITS as usize];
+        let mask = 1usize << (adjusted_index as u32 % usize::BITS);
+        let was_set = *word & mask != 0;
+        *word &= !mask;
+        was_set
+    }
+
+    /// returns the number of indices currently recorded in the window
+    pub fn len(&self) -> usize {
+        self.map.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// returns the number of inserted indices at or above `index`, counting from the start of the window if `index` is
+    /// below it
+    pub fn count_since(&self, index: u64) -> usize {
+        let offset = index.saturating_sub(self.first_index);
+        if offset >= Self::LEN as u64 {
+            return 0;
+        }
+        let word_idx = offset as usize / usize::BITS as usize;
+        let first = self.map[word_idx] & (usize::MAX << (offset % usize::BITS as u64));
+        let rest: usize = self.map[word_idx + 1..].iter().map(|word| word.count_ones() as usize).sum();
+        first.count_ones() as usize + rest
+    }
+
+    /// returns true if no indices are recorded in the window
+    pub fn is_empty(&self) -> bool {
+        self.map.iter().all(|word| *word == 0)
+    }
+
+    /// returns the fraction of the window's span that has been inserted, between 0 and 1
+    pub fn fill_ratio(&self) -> f32 {
+        self.len() as f32 / Self::LEN as f32
+    }
+
+    /// returns true if more than `threshold` of the window's span has been inserted, see [`Window::fill_ratio`]
+    pub fn is_crowded(&self, threshold: f32) -> bool {
+        self.fill_ratio() > threshold
+    }
+
+    /// Forget every index below `new_first`, moving the window forward. Does nothing if `new_first` is not past the
+    /// start of the window.
+    ///
+    /// The window moves a whole word at a time, so it will start at the word containing `new_first`. Forgotten indices
+    /// in that word can be inserted again, lower ones are rejected like any other index below the window.
+    pub fn advance_to(&mut self, new_first: u64) {
+        let Some(offset) = new_first.checked_sub(self.first_index) else { return };
+        let shift = (offset / usize::BITS as u64).min(N as u64) as usize;
+        self.map.copy_within(shift.., 0);
+        self.map[N - shift..].fill(0);
+        self.first_index += (offset / usize::BITS as u64) * usize::BITS as u64;
+        if let Some(word) = self.map.first_mut() {
+            *word &= !((1usize << (offset % usize::BITS as u64)) - 1);
+        }
+    }
+
+    /// Checks the window's internal invariants: every set bit must stand for an index that fits in a `u64`.
+    ///
+    /// Returns [`WindowDecodeError::PastEnd`] for a window [`Window::iter`] and [`Window::highest_seen`] would overflow
+    /// on. Inserting and decoding never build one, only [`Window::from_raw`] can.
+    pub fn validate(&self) -> Result<(), WindowDecodeError> {
+        if past_end(&self.map, self.first_index) {
+            return Err(WindowDecodeError::PastEnd);
+        }
+        Ok(())
+    }
+
+    /// clear every index and the rejection counters, and move the window back to 0
+    pub fn reset(&mut self) {
+        self.reset_to(0);
+    }
+
+    /// clear every index and the rejection counters, and move the window to start at `first`
+    pub fn reset_to(&mut self, first: u64) {
+        self.map.fill(0);
+        self.first_index = first;
+        self.duplicates = 0;
+        self.too_old = 0;
+    }
+
+    /// returns the highest index inserted into the window, or None if the window is empty
+    pub fn highest_seen(&self) -> Option<u64> {
+        let (word_idx, word) = self.map.iter().enumerate().rev().find(|(_, word)| **word != 0)?;
+        let bit = usize::BITS - 1 - word.leading_zeros();
+        Some(self.first_index + (word_idx * usize::BITS as usize) as u64 + bit as u64)
+    }
+
+    /// returns the lowest index at or above the start of the window that has not been inserted
+    pub fn next_expected(&self) -> u64 {
+        let missing = self.map.iter().enumerate().find(|(_, word)| **word != usize::MAX);
+        let bit = match missing {
+            Some((word_idx, word)) => word_idx * usize::BITS as usize + word.trailing_ones() as usize,
+            None => Self::LEN,
+        };
+        self.first_index + bit as u64
+    }
+
+    /// iterate over the indices from the start of the window up to the highest inserted index that have not been
+    /// inserted
+    pub fn gaps(&self) -> GapIter<'_, N> {
+        GapIter {
+            window: self,
+            idx: self.highest_seen().map(|_| self.first_index),
+            last: self.highest_seen().unwrap_or(self.first_index),
+        }
+    }
+
+    /// iterate over the indices from the start of the window up to and including `ceiling` that have not been inserted,
+    /// even above the highest inserted index. `ceiling` is clamped to [`Window::last_index`]
+    pub fn missing_up_to(&self, ceiling: u64) -> GapIter<'_, N> {
+        GapIter {
+            window: self,
+            idx: Some(self.first_index),
+            last: ceiling.min(self.last_index()),
+        }
+    }
+
+    /// classify the gaps below the highest index seen with the default [`CongestionThresholds`]
+    pub fn congestion_hint(&self) -> CongestionHint {
+        self.congestion_hint_with(CongestionThresholds::default())
+    }
+
+    /// Classify the gaps below the highest index seen, counting from the start of the window.
+    ///
+    /// Any gap is at least [`CongestionHint::Reordering`], and it's [`CongestionHint::Loss`] once a run of gaps reaches
+    /// `thresholds.loss_run` or the fraction missing exceeds `thresholds.loss_ratio`.
+    pub fn congestion_hint_with(&self, thresholds: CongestionThresholds) -> CongestionHint {
+        let Some(highest) = self.highest_seen() else { return CongestionHint::Healthy };
+        let mut missing = 0u64;
+        let mut run = 0u64;
+        let mut longest_run = 0u64;
+        let mut prev = None;
+        for gap in self.gaps() {
+            missing += 1;
+            run = if prev.map(|prev| prev + 1) == Some(gap) { run + 1 } else { 1 };
+            longest_run = longest_run.max(run);
+            prev = Some(gap);
+        }
+        let span = highest - self.first_index + 1;
+        if missing == 0 {
+            CongestionHint::Healthy
+        } else if longest_run >= thresholds.loss_run || missing as f32 / span as f32 > thresholds.loss_ratio {
+            CongestionHint::Loss
+        } else {
+            CongestionHint::Reordering
+        }
+    }
+
+    /// coalesce the inserted indices into inclusive `(start, end)` ranges, in ascending order
+    pub fn ack_ranges(&self) -> Vec<(u64, u64)> {
+        let mut ranges: Vec<(u64, u64)> = Vec::new();
+        for idx in self.iter() {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == idx => *end = idx,
+                _ => ranges.push((idx, idx)),
+            }
+        }
+        ranges
+    }
+
+    /// Write as many of [`Window::ack_ranges`] as fit in `max_bytes` to `dest`, returning how many were written.
+    ///
+    /// See [`write_ranges`] for the layout. Continue in the next packet with `write_ranges(&ack_ranges[written..], ..)`.
+    #[cfg(feature = "bytes")]
+    pub fn write_ack_ranges(&self, dest: &mut impl bytes::BufMut, max_bytes: usize) -> usize {
+        write_ranges(&self.ack_ranges(), dest, max_bytes)
+    }
+
+    /// Insert every index recorded in `other`, sliding this window forward as needed.
+    ///
+    /// Indices in `other` below the start of this window are dropped. Overlapping indices aren't counted as rejected,
+    /// see [`Window::duplicates_rejected`].
+    pub fn merge(&mut self, other: &Window<N>) {
+        for idx in other.iter() {
+            self.set(idx);
+        }
+    }
+
+    /// returns the indices recorded in this window that are not recorded in `prev`, in ascending order
+    pub fn difference(&self, prev: &Window<N>) -> Vec<u64> {
+        self.iter().filter(|idx| !prev.contains(*idx)).collect()
+    }
+
+    /// returns true if both windows have recorded the same indices, regardless of where each window starts
+    pub fn eq_logical(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+
+    /// serialize the window: `first_index` and `N` as varints, followed by each word in little endian
+    ///
+    /// Words are the size of a `usize`, use [`Window64`] for a layout that's the same on every target.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_map(&self.map, self.first_index)
+    }
+
+    /// rebuild a window serialized by [`Window::to_bytes`], rejecting any that sets an index past `u64::MAX`
+    pub fn from_bytes(src: &[u8]) -> Result<Self, WindowDecodeError> {
+        let (map, first_index) = decode_map(src)?;
+        Ok(Self::from_raw(map, first_index))
+    }
+
+    /// Serialize only the part of the window up to the highest inserted index: `first_index` and the number of bits as
+    /// varints, followed by just enough bytes of the bitmap (in little endian) to hold those bits.
+    ///
+    /// Unlike [`Window::to_bytes`], this is the same on every target.
+    pub fn export_compact(&self) -> Vec<u8> {
+        let bits = self.highest_seen().map_or(0, |idx| idx - self.first_index + 1);
+        let byte_len = bits.div_ceil(8) as usize;
+        let mut out = Vec::with_capacity(18 + byte_len);
+        encode_varints(&[self.first_index, bits], &mut out);
+        let bytes = self.map.iter().flat_map(|word| word.to_le_bytes());
+        out.extend(bytes.take(byte_len));
+        out
+    }
+
+    /// rebuild a window serialized by [`Window::export_compact`], rejecting any that sets an index past `u64::MAX`
+    pub fn import_compact(src: &[u8]) -> Result<Self, WindowDecodeError> {
+        let (first_index, len) = decode_varint_progress(src).map_err(|_| WindowDecodeError::InvalidLength)?;
+        let src = &src[len..];
+        let (bits, len) = decode_varint_progress(src).map_err(|_| WindowDecodeError::InvalidLength)?;
+        if bits > Self::LEN as u64 {
+            return Err(WindowDecodeError::WrongSize { expected: N, found: bits.div_ceil(usize::BITS as u64) });
+        }
+        let src = &src[len..];
+        if src.len() as u64 != bits.div_ceil(8) {
+            return Err(WindowDecodeError::InvalidLength);
+        }
+        let word_size = core::mem::size_of::<usize>();
+        let mut map = [0; N];
+        for (word, bytes) in map.iter_mut().zip(src.chunks(word_size)) {
+            let mut buf = [0; core::mem::size_of::<usize>()];
+            buf[..bytes.len()].copy_from_slice(bytes);
+            *word = usize::from_le_bytes(buf);
+        }
+        if past_end(&map, first_index) {
+            return Err(WindowDecodeError::PastEnd);
+        }
+        Ok(Self::from_raw(map, first_index))
     }
 
     pub fn iter<'a>(&'a self) -> Iter<'a, N> {
         Iter {
             window: self,
-            idx: self.first_index
+            front: 0,
+            back: Self::LEN,
         }
     }
 }
 
+impl<const N: usize> Default for Window<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<const N: usize> Debug for Window<N> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         struct Entries<'a, const N: usize>(&'a Window<N>);
         impl<const N: usize> Debug for Entries<'_, N> {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
                 f.debug_set().entries(self.0.iter()).finish()
             }
         }
@@ -106,66 +713,1112 @@ impl<const N: usize> Debug for Window<N> {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use rand::Rng;
+/// A bitmap window like [`Window`], with the number of words chosen at runtime
+#[derive(Clone, PartialEq, Eq)]
+pub struct DynWindow {
+    map: Box<[usize]>,
+    first_index: u64,
+}
 
-    use super::*;
+pub struct DynIter<'a> {
+    window: &'a DynWindow,
+    idx: u64,
+}
 
-    #[test]
-    fn simple() {
-        let mut window = Window::<2>::new();
-        for i in 0..128 {
-            assert_eq!(window.insert(i), true);
-        }
+impl Iterator for DynIter<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let idx = next_set(&self.window.map, self.window.first_index, self.idx)?;
+        self.idx = idx + 1;
+        Some(idx)
     }
+}
 
-    #[test]
-    fn large() {
-        let mut window = Window::<10>::new();
-        for i in 0..(10 * 64) {
-            assert_eq!(window.insert(i), true);
+impl DynWindow {
+    /// create a new, empty window of `words` words. Panics if `words` is 0.
+    pub fn new(words: usize) -> Self {
+        assert!(words > 0, "a window needs at least one word");
+        Self {
+            map: vec![0; words].into_boxed_slice(),
+            first_index: 0,
         }
     }
 
-    #[test]
-    fn expanding() {
-        let mut window = Window::<3>::new();
-        for i in 0..(10 * 64) {
-            assert_eq!(window.insert(i), true);
+    /// returns true if index can be inserted
+    pub fn can_insert(&self, index: u64) -> bool {
+        let adjusted_index = match index.checked_sub(self.first_index) {
+            Some(offset) => offset,
+            None => return false
+        };
+        let word_idx = adjusted_index as usize / usize::BITS as usize;
+        let word_offset = adjusted_index as u32 % usize::BITS;
+        let mask = 1usize << word_offset;
+        match self.map.get(word_idx) {
+            Some(word) => word & mask == 0,
+            None => true,
         }
     }
 
-    #[test]
-    fn expanding_with_skips() {
-        let mut window = Window::<5>::new();
-        for i in (0..(100 * 64)).step_by(100) {
-            assert_eq!(window.insert(i), true);
-            assert_eq!(window.insert(i), false);
+    /// Attemps to insert `index`, see [`Window::insert`].
+    pub fn insert(&mut self, index: u64) -> bool {
+        let adjusted_index = match index.checked_sub(self.first_index) {
+            Some(offset) => offset,
+            None => return false
+        };
+        let mut word_idx = adjusted_index as usize / usize::BITS as usize;
+        let word_offset = adjusted_index as u32 % usize::BITS;
+        if word_idx >= self.map.len() {
+            word_idx = slide(&mut self.map, &mut self.first_index, word_idx);
         }
+
+        let word = &mut self.map[word_idx];
+        let mask = 1usize << word_offset;
+        let new = (*word & mask) == 0;
+        *word |= mask;
+        new
     }
 
-    #[test]
-    fn expanding_with_big_skips() {
-        let mut window = Window::<5>::new();
-        for i in (0..(1000 * 64)).step_by(1000) {
-            assert_eq!(window.insert(i), true, "{i}");
-            assert_eq!(window.insert(i), false, "{i}");
-            assert_eq!(window.insert(i+1), true, "{i}");
-            assert_eq!(window.insert(i+1), false, "{i}");
-            assert_eq!(window.insert(i+128), true, "{i}");
-            assert_eq!(window.insert(i+128), false, "{i}");
-            assert_eq!(window.insert(i), false, "{window:?} {i}");
-            assert_eq!(window.insert(i+1), false, "{i}");
+    pub fn iter(&self) -> DynIter<'_> {
+        DynIter {
+            window: self,
+            idx: self.first_index
         }
     }
-    #[test]
-    fn expanding_with_random() {
-        let mut window = Window::<5>::new();
-        let mut r = rand::thread_rng();
-        let mut nums = Vec::from_iter(std::iter::repeat_with(|| r.gen_range(0..1_000_000)).take(100_000));
-        nums.sort_unstable();
-        nums.dedup();
+}
+
+impl Debug for DynWindow {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        struct Entries<'a>(&'a DynWindow);
+        impl Debug for Entries<'_> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.debug_set().entries(self.0.iter()).finish()
+            }
+        }
+        f.debug_struct("DynWindow").field("con", &Entries(self)).field("first_index", &self.first_index).finish()
+    }
+}
+
+/// A bitmap window like [`Window`], which always uses 64 bit words.
+///
+/// The span of a [`Window`] depends on the target's pointer width, this window spans `N * 64` indices everywhere.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Window64<const N: usize = 3> {
+    map: [u64; N],
+    first_index: u64,
+}
+
+pub struct Iter64<'a, const N: usize> {
+    window: &'a Window64<N>,
+    idx: u64,
+}
+
+impl<const N: usize> Iterator for Iter64<'_, N> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let idx = next_set(&self.window.map, self.window.first_index, self.idx)?;
+        self.idx = idx + 1;
+        Some(idx)
+    }
+}
+
+impl<const N: usize> Window64<N> {
+    const LEN: usize = N * u64::BITS as usize;
+    const NOT_EMPTY: () = assert!(N > 0, "a window needs at least one word");
+
+    /// create a new, empty window
+    pub fn new() -> Self {
+        let () = Self::NOT_EMPTY;
+        Self {
+            map: [0; N],
+            first_index: 0,
+        }
+    }
+
+    /// returns the lowest index the window can hold, lower indices can't be inserted
+    pub fn first_index(&self) -> u64 {
+        self.first_index
+    }
+
+    /// returns the highest index the window can hold without sliding, which is at most `u64::MAX`
+    pub fn last_index(&self) -> u64 {
+        self.first_index.saturating_add(Self::LEN as u64 - 1)
+    }
+
+    /// returns the number of indices the window spans
+    pub fn capacity(&self) -> usize {
+        Self::LEN
+    }
+
+    /// returns true if index can be inserted
+    pub fn can_insert(&self, index: u64) -> bool {
+        let adjusted_index = match index.checked_sub(self.first_index) {
+            Some(offset) => offset,
+            None => return false
+        };
+        match self.map.get((adjusted_index / u64::BITS as u64) as usize) {
+            Some(word) => word & (1 << (adjusted_index % u64::BITS as u64)) == 0,
+            None => true,
+        }
+    }
+
+    /// returns true if index has been inserted and is still within the window
+    pub fn contains(&self, index: u64) -> bool {
+        match index.checked_sub(self.first_index) {
+            Some(offset) if offset < Self::LEN as u64 => !self.can_insert(index),
+            _ => false,
+        }
+    }
+
+    /// Attemps to insert `index`, see [`Window::insert`].
+    pub fn insert(&mut self, index: u64) -> bool {
+        let adjusted_index = match index.checked_sub(self.first_index) {
+            Some(offset) => offset,
+            None => return false
+        };
+        let mut word_idx = (adjusted_index / u64::BITS as u64).try_into().unwrap_or(usize::MAX);
+        let word_offset = adjusted_index % u64::BITS as u64;
+        if word_idx >= N {
+            word_idx = slide(&mut self.map, &mut self.first_index, word_idx);
+        }
+
+        let word = &mut self.map[word_idx];
+        let mask = 1u64 << word_offset;
+        let new = (*word & mask) == 0;
+        *word |= mask;
+        new
+    }
+
+    /// serialize the window, in the same layout as [`Window::to_bytes`] but always with 64 bit words
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_map(&self.map, self.first_index)
+    }
+
+    /// rebuild a window serialized by [`Window64::to_bytes`], rejecting any that sets an index past `u64::MAX`
+    pub fn from_bytes(src: &[u8]) -> Result<Self, WindowDecodeError> {
+        let (map, first_index) = decode_map(src)?;
+        let mut window = Self::new();
+        window.map = map;
+        window.first_index = first_index;
+        Ok(window)
+    }
+
+    pub fn iter(&self) -> Iter64<'_, N> {
+        Iter64 {
+            window: self,
+            idx: self.first_index
+        }
+    }
+}
+
+impl<const N: usize> Default for Window64<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Debug for Window64<N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        struct Entries<'a, const N: usize>(&'a Window64<N>);
+        impl<const N: usize> Debug for Entries<'_, N> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.debug_set().entries(self.0.iter()).finish()
+            }
+        }
+        f.debug_struct("Window64").field("con", &Entries(self)).field("first_index", &self.first_index).finish()
+    }
+}
+
+/// A [`Window`] over sequence numbers that wrap around, like a 32 bit packet number.
+///
+/// Each truncated sequence number is unwrapped to the full `u64` index nearest to the next expected index, so numbers
+/// more than half the sequence space behind are treated as being ahead, and vice versa. Until something is inserted,
+/// the next expected index is the base passed to [`WrappingWindow::with_base`], or for [`WrappingWindow::new`] whatever
+/// sequence number comes first, so flows can start anywhere in the sequence space.
+///
+/// Indices more than [`WrappingWindow::set_max_distance`] away from the next expected index in either direction are
+/// rejected rather than evicting the whole window. The default is a quarter of the sequence space.
+#[derive(Clone, Debug)]
+pub struct WrappingWindow<const N: usize = 3> {
+    window: Window<N>,
+    bits: u32,
+    base: Option<u64>,
+    max_distance: u64,
+}
+
+impl<const N: usize> WrappingWindow<N> {
+    /// create a new, empty window for sequence numbers `bits` wide, starting from the first sequence number inserted.
+    /// Panics if `bits` is not in `1..=64`.
+    pub fn new(bits: u32) -> Self {
+        assert!(matches!(bits, 1..=64), "sequence numbers must be between 1 and 64 bits wide, got {bits}");
+        Self {
+            window: Window::new(),
+            bits,
+            base: None,
+            max_distance: ((1u64 << (bits - 1)) / 2).max(1),
+        }
+    }
+
+    /// create a new, empty window for sequence numbers `bits` wide that expects the index `base` next. Panics if `bits`
+    /// is not in `1..=64`.
+    pub fn with_base(bits: u32, base: u64) -> Self {
+        Self { base: Some(base), ..Self::new(bits) }
+    }
+
+    /// reject indices more than `max_distance` ahead of or behind the next expected index
+    pub fn set_max_distance(&mut self, max_distance: u64) {
+        self.max_distance = max_distance;
+    }
+
+    /// Returns the full index for a truncated sequence number, or None if `seq` is wider than the sequence space,
+    /// would unwrap to a negative index, or unwraps further than the maximum distance from the next expected index.
+    pub fn unwrap(&self, seq: u64) -> Option<u64> {
+        if self.bits < 64 && seq >> self.bits != 0 {
+            return None;
+        }
+        // nothing follows u64::MAX, so after seeing it keep expecting it
+        let expected = self.window.highest_seen().map(|idx| idx.saturating_add(1)).or(self.base);
+        let Some(expected) = expected else { return Some(seq) };
+        let candidate = if self.bits == 64 {
+            seq
+        } else {
+            let span = 1u64 << self.bits;
+            let half = span / 2;
+            let candidate = (expected & !(span - 1)) | seq;
+            if candidate.saturating_add(half) <= expected {
+                candidate.checked_add(span)?
+            } else if candidate > expected.saturating_add(half) {
+                candidate.checked_sub(span)?
+            } else {
+                candidate
+            }
+        };
+        (candidate.abs_diff(expected) <= self.max_distance).then_some(candidate)
+    }
+
+    /// Attempts to insert a truncated sequence number, see [`Window::insert`].
+    pub fn insert(&mut self, seq: u64) -> bool {
+        match self.unwrap(seq) {
+            Some(idx) => self.window.insert(idx),
+            None => false,
+        }
+    }
+
+    /// returns the underlying window of unwrapped indices
+    pub fn window(&self) -> &Window<N> {
+        &self.window
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn simple() {
+        let mut window = Window::<2>::new();
+        for i in 0..128 {
+            assert!(window.insert(i));
+        }
+    }
+
+    #[test]
+    fn large() {
+        let mut window = Window::<10>::new();
+        for i in 0..(10 * 64) {
+            assert!(window.insert(i));
+        }
+    }
+
+    #[test]
+    fn expanding() {
+        let mut window = Window::<3>::new();
+        for i in 0..(10 * 64) {
+            assert!(window.insert(i));
+        }
+    }
+
+    #[test]
+    fn expanding_with_skips() {
+        let mut window = Window::<5>::new();
+        for i in (0..(100 * 64)).step_by(100) {
+            assert!(window.insert(i));
+            assert!(!window.insert(i));
+        }
+    }
+
+    #[test]
+    fn expanding_with_big_skips() {
+        let mut window = Window::<5>::new();
+        for i in (0..(1000 * 64)).step_by(1000) {
+            assert!(window.insert(i), "{i}");
+            assert!(!window.insert(i), "{i}");
+            assert!(window.insert(i+1), "{i}");
+            assert!(!window.insert(i+1), "{i}");
+            assert!(window.insert(i+128), "{i}");
+            assert!(!window.insert(i+128), "{i}");
+            assert!(!window.insert(i), "{window:?} {i}");
+            assert!(!window.insert(i+1), "{i}");
+        }
+    }
+    #[test]
+    fn reset() {
+        let mut window = Window::<3>::new();
+        for i in 0..1000 {
+            assert!(window.insert(i));
+        }
+        window.reset();
+        assert_eq!(window.iter().next(), None);
+        for i in 0..100 {
+            assert!(window.insert(i));
+        }
+
+        window.reset_to(5000);
+        assert!(!window.can_insert(4999));
+        assert!(window.insert(5000));
+        assert!(!window.insert(5000));
+        assert_eq!(window.iter().collect::<Vec<_>>(), [5000]);
+    }
+
+    #[test]
+    fn contains() {
+        let mut window = Window::<3>::new();
+        window.insert(10);
+        assert!(window.contains(10));
+        assert!(!window.contains(11));
+        assert!(!window.contains(u64::MAX));
+
+        window.insert(10_000);
+        assert!(window.contains(10_000));
+        assert!(!window.contains(10));
+        assert!(!window.can_insert(10));
+    }
+
+    #[test]
+    fn len() {
+        let mut window = Window::<3>::new();
+        assert_eq!(window.len(), 0);
+        assert!(window.is_empty());
+        for i in [0, 1, 5, 63, 64, 100, 191] {
+            window.insert(i);
+        }
+        window.insert(5);
+        assert_eq!(window.len(), 7);
+        assert!(!window.is_empty());
+    }
+
+    #[test]
+    fn gaps() {
+        let mut window = Window::<3>::new();
+        assert_eq!(window.gaps().next(), None);
+        for i in [0, 1, 3, 6] {
+            window.insert(i);
+        }
+        assert_eq!(window.gaps().collect::<Vec<_>>(), [2, 4, 5]);
+
+        let mut window = Window::<3>::new();
+        for i in 0..150 {
+            window.insert(i);
+        }
+        assert_eq!(window.gaps().next(), None);
+        window.insert(200);
+        assert_eq!(window.gaps().collect::<Vec<_>>(), Vec::from_iter(150..200));
+    }
+
+    #[test]
+    fn highest_seen_and_next_expected() {
+        let mut window = Window::<3>::new();
+        assert_eq!(window.highest_seen(), None);
+        assert_eq!(window.next_expected(), 0);
+
+        window.insert(0);
+        assert_eq!(window.highest_seen(), Some(0));
+        assert_eq!(window.next_expected(), 1);
+
+        for i in [1, 2, 70, 130] {
+            window.insert(i);
+        }
+        assert_eq!(window.highest_seen(), Some(130));
+        assert_eq!(window.next_expected(), 3);
+
+        for i in 0..192 {
+            window.insert(i);
+        }
+        assert_eq!(window.highest_seen(), Some(191));
+        assert_eq!(window.next_expected(), 192);
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let mut window = Window::<3>::new();
+        for i in (0..1000).step_by(7) {
+            window.insert(i);
+        }
+        let bytes = window.to_bytes();
+        let restored = Window::<3>::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.iter().collect::<Vec<_>>(), window.iter().collect::<Vec<_>>());
+        for i in 0..1200 {
+            assert_eq!(restored.can_insert(i), window.can_insert(i), "{i}");
+            assert_eq!(restored.contains(i), window.contains(i), "{i}");
+        }
+
+        assert_eq!(
+            Window::<4>::from_bytes(&bytes).unwrap_err(),
+            WindowDecodeError::WrongSize { expected: 4, found: 3 }
+        );
+        assert_eq!(Window::<3>::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(), WindowDecodeError::InvalidLength);
+        assert_eq!(Window::<3>::from_bytes(&[]).unwrap_err(), WindowDecodeError::InvalidLength);
+
+        let last = Window::<2>::from_raw([1, 0], u64::MAX);
+        assert_eq!(Window::<2>::from_bytes(&last.to_bytes()), Ok(last));
+        let hostile = Window::<2>::from_raw([0, 1 << 5], u64::MAX - 10);
+        assert_eq!(Window::<2>::from_bytes(&hostile.to_bytes()), Err(WindowDecodeError::PastEnd));
+    }
+
+    #[test]
+    fn unset() {
+        let mut window = Window::<3>::new();
+        assert!(window.insert(42));
+        assert!(window.unset(42));
+        assert!(!window.unset(42));
+        assert!(window.can_insert(42));
+        assert!(window.insert(42));
+
+        window.insert(1000);
+        assert!(!window.unset(42));
+        assert!(!window.unset(u64::MAX));
+        assert!(!window.can_insert(42));
+    }
+
+    #[test]
+    fn ack_ranges() {
+        let mut window = Window::<3>::new();
+        assert_eq!(window.ack_ranges(), []);
+
+        for i in [0, 1, 2, 5, 6, 9] {
+            window.insert(i);
+        }
+        assert_eq!(window.ack_ranges(), [(0, 2), (5, 6), (9, 9)]);
+
+        window.reset();
+        for i in 10..150 {
+            window.insert(i);
+        }
+        assert_eq!(window.ack_ranges(), [(10, 149)]);
+    }
+
+    #[test]
+    fn clone_and_eq() {
+        let mut window = Window::<3>::new();
+        for i in [3, 60, 100] {
+            window.insert(i);
+        }
+        let mut copy = window.clone();
+        assert_eq!(copy, window);
+        copy.insert(4);
+        assert_ne!(copy, window);
+        assert!(window.can_insert(4));
+
+        let mut slid = Window::<3>::new();
+        slid.reset_to(64);
+        slid.insert(100);
+        let mut unslid = Window::<3>::new();
+        unslid.insert(100);
+        assert_ne!(slid, unslid);
+        assert!(slid.eq_logical(&unslid));
+        unslid.insert(0);
+        assert!(!slid.eq_logical(&unslid));
+    }
+
+    #[test]
+    fn merge() {
+        let mut a = Window::<3>::new();
+        let mut b = Window::<3>::new();
+        for i in [1, 2, 3, 50] {
+            a.insert(i);
+        }
+        for i in [3, 4, 100, 150] {
+            b.insert(i);
+        }
+        a.merge(&b);
+        assert_eq!(a.iter().collect::<Vec<_>>(), [1, 2, 3, 4, 50, 100, 150]);
+
+        let mut ahead = Window::<3>::new();
+        ahead.reset_to(128);
+        ahead.insert(130);
+        ahead.insert(250);
+        a.merge(&ahead);
+        assert_eq!(a.iter().collect::<Vec<_>>(), [100, 130, 150, 250]);
+
+        ahead.merge(&b);
+        assert_eq!(ahead.iter().collect::<Vec<_>>(), [130, 150, 250]);
+    }
+
+    #[test]
+    fn even_sized_slide() {
+        for words in [1, 2, 4] {
+            let mut window = DynWindow::new(words);
+            for i in (0..(100 * 64)).step_by(37) {
+                assert!(window.insert(i), "{window:?} {i}");
+                assert!(!window.insert(i), "{window:?} {i}");
+            }
+        }
+        let mut window = Window::<2>::new();
+        for i in 0..1000 {
+            assert!(window.insert(i));
+        }
+    }
+
+    #[test]
+    fn dyn_expanding_with_skips() {
+        let mut window = DynWindow::new(5);
+        for i in (0..(100 * 64)).step_by(100) {
+            assert!(window.insert(i));
+            assert!(!window.insert(i));
+        }
+    }
+
+    #[test]
+    fn dyn_expanding_with_random() {
+        let mut window = DynWindow::new(5);
+        let mut r = rand::thread_rng();
+        let mut nums = Vec::from_iter(std::iter::repeat_with(|| r.gen_range(0..1_000_000)).take(100_000));
+        nums.sort_unstable();
+        nums.dedup();
+
+        for chunk in nums.chunks(5000) {
+            for n in chunk {
+                assert!(window.can_insert(*n), "{window:?} {n}");
+                assert!(window.insert(*n), "{window:?} {n}");
+                assert!(!window.can_insert(*n), "{window:?} {n}");
+                assert!(!window.insert(*n), "{window:?} {n}");
+            }
+            for n in chunk {
+                assert!(!window.can_insert(*n), "{window:?} {n}");
+                assert!(!window.insert(*n), "{window:?} {n}");
+            }
+        }
+        for n in nums.iter() {
+            assert!(!window.can_insert(*n), "{window:?} {n}");
+            assert!(!window.insert(*n), "{window:?} {n}");
+        }
+    }
+
+    #[test]
+    fn dyn_matches_const() {
+        let mut window = Window::<5>::new();
+        let mut dyn_window = DynWindow::new(5);
+        let mut r = rand::thread_rng();
+        let mut idx = 0;
+        for _ in 0..10_000 {
+            idx += r.gen_range(0..200);
+            assert_eq!(window.insert(idx), dyn_window.insert(idx));
+        }
+        assert!(window.iter().eq(dyn_window.iter()));
+    }
+
+    #[test]
+    fn bounds() {
+        let mut window = Window::<3>::new();
+        let bits = usize::BITS as u64;
+        assert_eq!(window.capacity(), 3 * bits as usize);
+        assert_eq!(window.first_index(), 0);
+        assert_eq!(window.last_index(), 3 * bits - 1);
+
+        window.insert(window.last_index());
+        assert_eq!(window.first_index(), 0);
+
+        window.insert(3 * bits);
+        assert_eq!(window.first_index(), bits);
+        assert_eq!(window.last_index(), 4 * bits - 1);
+        assert!(window.contains(3 * bits));
+        assert!(window.contains(3 * bits - 1));
+
+        window.insert(window.last_index() + 10 * bits);
+        assert_eq!(window.first_index(), 11 * bits);
+        assert!(window.contains(14 * bits - 1));
+        assert_eq!(window.capacity(), 3 * bits as usize);
+
+        window.reset_to(u64::MAX);
+        assert_eq!(window.last_index(), u64::MAX);
+        assert!(window.insert(u64::MAX));
+        assert!(window.contains(u64::MAX));
+        assert_eq!(window.first_index(), u64::MAX);
+    }
+
+    #[test]
+    fn insert_sorted() {
+        let mut window = Window::<3>::new();
+        let indices = [1, 2, 2, 3, 10, 10, 10, 100, 150, 300];
+        assert_eq!(window.insert_sorted(indices), 7);
+        for i in indices {
+            assert!(!window.can_insert(i), "{i}");
+        }
+        assert_eq!(window.insert_sorted([300, 301]), 1);
+    }
+
+    #[test]
+    fn insert_range() {
+        let mut window = Window::<3>::new();
+        let mut looped = Window::<3>::new();
+        assert_eq!(window.insert_range(0, 200), 201);
+        assert_eq!(looped.insert_sorted(0..=200), 201);
+        assert_eq!(window, looped);
+        assert_eq!(window.first_index(), usize::BITS as u64);
+
+        assert_eq!(window.insert_range(150, 210), 10);
+        assert_eq!(window.insert_range(0, 10), 0);
+        assert_eq!(window.insert_range(5, 4), 0);
+        looped.insert_sorted(150..=210);
+        assert_eq!(window, looped);
+
+        // start the counters off equal too
+        let mut looped = window.clone();
+        let mut r = rand::thread_rng();
+        for _ in 0..1000 {
+            let start = r.gen_range(0..2000);
+            let end = start + r.gen_range(0..300);
+            assert_eq!(window.insert_range(start, end), looped.insert_sorted(start..=end) as u64);
+            assert_eq!(window, looped);
+            assert_eq!(window.duplicates_rejected(), looped.duplicates_rejected());
+            assert_eq!(window.too_old_rejected(), looped.too_old_rejected());
+        }
+
+        let mut window = Window::<3>::new();
+        window.reset_to(u64::MAX - 100);
+        assert_eq!(window.insert_range(u64::MAX - 10, u64::MAX), 11);
+        assert!(window.contains(u64::MAX));
+    }
+
+    #[test]
+    fn difference() {
+        let mut prev = Window::<3>::new();
+        prev.insert_sorted([0, 1, 2]);
+        let mut window = prev.clone();
+        window.insert_sorted([3, 5]);
+        assert_eq!(window.difference(&prev), [3, 5]);
+        assert_eq!(prev.difference(&window), [0u64; 0]);
+
+        let prev = window.clone();
+        window.insert_sorted([100, 250]);
+        assert_eq!(window.difference(&prev), [100, 250]);
+    }
+
+    #[test]
+    fn window64_len() {
+        assert_eq!(Window64::<3>::LEN, 3 * 64);
+        assert_eq!(Window64::<5>::new().capacity(), 5 * 64);
+        assert_eq!(Window64::<5>::new().last_index(), 5 * 64 - 1);
+
+        let mut window = Window64::<5>::new();
+        window.first_index = u64::MAX;
+        assert_eq!(window.last_index(), u64::MAX);
+        window.map[0] = 0b10;
+        assert_eq!(Window64::<5>::from_bytes(&window.to_bytes()), Err(WindowDecodeError::PastEnd));
+
+        let mut window = Window64::<3>::new();
+        for i in [1, 100, 1000] {
+            window.insert(i);
+        }
+        let bytes = window.to_bytes();
+        assert_eq!(bytes.len(), 2 + 1 + 3 * 8);
+        assert_eq!(Window64::<3>::from_bytes(&bytes), Ok(window));
+    }
+
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn window64_matches_window() {
+        let mut window = Window::<5>::new();
+        let mut window64 = Window64::<5>::new();
+        let mut r = rand::thread_rng();
+        let mut idx = 0u64;
+        for _ in 0..10_000 {
+            idx += r.gen_range(0..200);
+            let back = idx.saturating_sub(r.gen_range(0..400));
+            assert_eq!(window.can_insert(back), window64.can_insert(back));
+            assert_eq!(window.contains(back), window64.contains(back));
+            assert_eq!(window.insert(idx), window64.insert(idx));
+            assert_eq!(window.first_index(), window64.first_index());
+        }
+        assert!(window.iter().eq(window64.iter()));
+
+        // both reject the same bits past u64::MAX
+        for bit in [10, 11] {
+            let mut window64 = Window64::<5>::new();
+            window64.first_index = u64::MAX - 10;
+            window64.map[0] = 1 << bit;
+            let bytes = window64.to_bytes();
+            let valid = bit == 10;
+            assert_eq!(Window64::<5>::from_bytes(&bytes).is_ok(), valid);
+            assert_eq!(Window::<5>::from_bytes(&bytes).is_ok(), valid);
+        }
+    }
+
+    #[test]
+    fn double_ended_iter() {
+        let mut window = Window::<3>::new();
+        let indices = [0, 3, 63, 64, 100, 191];
+        for i in indices {
+            window.insert(i);
+        }
+        assert!(window.iter().rev().eq(indices.iter().rev().copied()));
+
+        let mut iter = window.iter();
+        assert_eq!(iter.next_back(), Some(191));
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.next_back(), Some(100));
+        let mut rest: Vec<_> = iter.by_ref().collect();
+        rest.sort_unstable();
+        assert_eq!(rest, [3, 63, 64]);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+
+        let mut window = Window::<3>::new();
+        window.reset_to(u64::MAX - 10);
+        window.insert(u64::MAX);
+        assert_eq!(window.iter().next_back(), Some(u64::MAX));
+    }
+
+    #[test]
+    fn fill_ratio() {
+        let mut window = Window::<4>::new();
+        assert_eq!(window.fill_ratio(), 0.0);
+        assert!(!window.is_crowded(0.0));
+
+        window.insert_range(0, window.last_index() / 4);
+        assert!((window.fill_ratio() - 0.25).abs() < 0.01);
+        assert!(window.is_crowded(0.2));
+        assert!(!window.is_crowded(0.3));
+
+        window.insert_range(0, window.last_index());
+        assert_eq!(window.fill_ratio(), 1.0);
+    }
+
+    #[test]
+    fn advance_to() {
+        let bits = usize::BITS as u64;
+        let mut window = Window::<3>::new();
+        let indices = [1, 10, 70, bits + 10, bits + 20, 2 * bits + 5];
+        for i in indices {
+            window.insert(i);
+        }
+        window.advance_to(0);
+        assert_eq!(window.iter().collect::<Vec<_>>(), indices);
+
+        window.advance_to(bits + 15);
+        assert_eq!(window.first_index(), bits);
+        assert_eq!(window.iter().collect::<Vec<_>>(), [bits + 20, 2 * bits + 5]);
+        assert!(!window.can_insert(10));
+        assert!(window.can_insert(bits + 10));
+        assert!(!window.can_insert(bits + 20));
+        assert!(!window.can_insert(2 * bits + 5));
+
+        window.advance_to(10 * bits);
+        assert_eq!(window.first_index(), 10 * bits);
+        assert!(window.is_empty());
+    }
+
+    #[test]
+    fn raw() {
+        let bits = usize::BITS as u64;
+        let window = Window::<3>::from_raw([0b101, 0, 1 << 3], 1000);
+        assert_eq!(window.iter().collect::<Vec<_>>(), [1000, 1002, 1000 + 2 * bits + 3]);
+
+        let mut window = Window::<3>::new();
+        window.insert_sorted([5, 70, 500]);
+        let (map, first_index) = window.raw();
+        assert_eq!(Window::from_raw(*map, first_index), window);
+    }
+
+    #[test]
+    fn rejection_counters() {
+        let mut window = Window::<3>::new();
+        let indices = [1, 5, 9, 100];
+        window.insert_sorted(indices);
+        assert_eq!(window.duplicates_rejected(), 0);
+        window.insert_sorted(indices);
+        window.insert_sorted(indices);
+        assert_eq!(window.duplicates_rejected(), 2 * indices.len() as u64);
+        assert_eq!(window.too_old_rejected(), 0);
+
+        window.insert(1000);
+        window.insert_sorted(indices);
+        assert_eq!(window.duplicates_rejected(), 2 * indices.len() as u64);
+        assert_eq!(window.too_old_rejected(), indices.len() as u64);
+
+        // 1000 is a duplicate, and everything below the window is too old
+        window.insert_range(0, 1001);
+        assert_eq!(window.duplicates_rejected(), 2 * indices.len() as u64 + 1);
+        assert_eq!(window.too_old_rejected(), indices.len() as u64 + window.first_index());
+
+        // merging overlapping windows isn't a rejection
+        let before = window.duplicates_rejected();
+        window.merge(&window.clone());
+        assert_eq!(window.duplicates_rejected(), before);
+
+        window.reset();
+        assert_eq!(window.duplicates_rejected(), 0);
+        assert_eq!(window.too_old_rejected(), 0);
+    }
+
+    #[test]
+    fn would_slide() {
+        let mut window = Window::<3>::new();
+        let mut r = rand::thread_rng();
+        let mut idx = 0u64;
+        for _ in 0..10_000 {
+            idx += r.gen_range(0..100);
+            let candidate = idx.saturating_sub(r.gen_range(0..300));
+            let before = window.first_index();
+            let predicted = window.would_slide(candidate);
+            window.insert(candidate);
+            assert_eq!(predicted, window.first_index() != before, "{window:?} {candidate}");
+        }
+        assert!(!window.would_slide(0));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn traces_slides() {
+        let bits = usize::BITS as u64;
+        let events = crate::trace_capture::capture(|| {
+            let mut window = Window::<2>::new();
+            window.insert(0);
+            window.insert(2 * bits - 1);
+            window.insert(3 * bits);
+        });
+        let expected = format!("window slid first_index={} evicted_words=2", 2 * bits);
+        assert_eq!(events, [(tracing::Level::TRACE, expected)]);
+    }
+
+    #[test]
+    fn missing_up_to() {
+        let mut window = Window::<1>::new();
+        for idx in [0, 1, 3, 5] {
+            window.insert(idx);
+        }
+        assert_eq!(window.highest_seen(), Some(5));
+        assert_eq!(window.gaps().collect::<Vec<_>>(), [2, 4]);
+        assert_eq!(window.missing_up_to(9).collect::<Vec<_>>(), [2, 4, 6, 7, 8, 9]);
+        assert_eq!(window.missing_up_to(3).collect::<Vec<_>>(), [2]);
+
+        let bits = usize::BITS as u64;
+        assert_eq!(window.missing_up_to(u64::MAX).last(), Some(bits - 1));
+        assert_eq!(window.missing_up_to(u64::MAX).count() as u64, bits - 4);
+        window.advance_to(bits);
+        assert_eq!(window.missing_up_to(3).count(), 0);
+
+        window.reset_to(u64::MAX - 10);
+        window.insert(u64::MAX - 5);
+        assert_eq!(window.missing_up_to(u64::MAX).count(), 10);
+        assert_eq!(window.missing_up_to(u64::MAX).last(), Some(u64::MAX));
+        assert_eq!(window.gaps().collect::<Vec<_>>(), (u64::MAX - 10..u64::MAX - 5).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn count_since() {
+        let mut r = rand::thread_rng();
+        let mut window = Window::<3>::new();
+        window.advance_to(1000);
+        for _ in 0..80 {
+            window.insert(window.first_index() + r.gen_range(0..window.capacity() as u64));
+        }
+        let first = window.first_index();
+        for threshold in [0, first, first + 1, first + 63, first + 64, first + 100, window.last_index(), u64::MAX] {
+            let expected = window.iter().filter(|idx| *idx >= threshold).count();
+            assert_eq!(window.count_since(threshold), expected, "{threshold}");
+        }
+        assert_eq!(window.count_since(0), window.len());
+    }
+
+    #[test]
+    fn congestion_hints() {
+        let mut window = Window::<4>::new();
+        assert_eq!(window.congestion_hint(), CongestionHint::Healthy);
+        window.insert_range(0, 200);
+        assert_eq!(window.congestion_hint(), CongestionHint::Healthy);
+
+        let mut scattered = window.clone();
+        for idx in (10..200).step_by(20) {
+            scattered.unset(idx);
+        }
+        assert_eq!(scattered.congestion_hint(), CongestionHint::Reordering);
+
+        let mut block = window.clone();
+        for idx in 50..60 {
+            block.unset(idx);
+        }
+        assert_eq!(block.congestion_hint(), CongestionHint::Loss);
+        let lenient = CongestionThresholds { loss_run: 20, ..Default::default() };
+        assert_eq!(block.congestion_hint_with(lenient), CongestionHint::Reordering);
+
+        // scattered, but too many missing
+        let strict = CongestionThresholds { loss_ratio: 0.01, ..Default::default() };
+        assert_eq!(scattered.congestion_hint_with(strict), CongestionHint::Loss);
+
+        // the first index of the window is a gap
+        let mut first_lost = Window::<4>::new();
+        first_lost.insert(5);
+        assert_eq!(first_lost.congestion_hint(), CongestionHint::Loss);
+        first_lost.insert_range(1, 4);
+        assert_eq!(first_lost.congestion_hint(), CongestionHint::Loss);
+        let lenient = CongestionThresholds { loss_run: 2, loss_ratio: 0.5 };
+        assert_eq!(first_lost.congestion_hint_with(lenient), CongestionHint::Reordering);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn write_ack_ranges_within_budget() {
+        use crate::varint::VarintIter;
+
+        let mut window = Window::<4>::new();
+        window.insert_range(0, 3);
+        window.insert_range(10, 20);
+        window.insert_range(150, 200);
+        window.insert(255);
+        assert_eq!(window.ack_ranges(), [(0, 3), (10, 20), (150, 200), (255, 255)]);
+
+        // 2 + 2 bytes for the first two ranges, the third takes 4 and doesn't fit in the 3 left
+        let mut buf = bytes::BytesMut::new();
+        assert_eq!(window.write_ack_ranges(&mut buf, 7), 2);
+        assert_eq!(buf.len(), 4);
+        let decoded: Result<Vec<u64>, _> = VarintIter::new(buf.clone().freeze()).collect();
+        assert_eq!(decoded, Ok(vec![0, 3, 10, 20]));
+
+        let rest = &window.ack_ranges()[2..];
+        let mut next = bytes::BytesMut::new();
+        assert_eq!(write_ranges(rest, &mut next, 7), 1);
+        assert_eq!(write_ranges(&rest[1..], &mut next, 3), 0);
+        assert_eq!(next.len(), 4);
+
+        let mut full = bytes::BytesMut::new();
+        assert_eq!(window.write_ack_ranges(&mut full, usize::MAX), 4);
+        assert_eq!(full.len(), 12);
+        let mut tiny = [0u8; 5];
+        assert_eq!(window.write_ack_ranges(&mut &mut tiny[..], usize::MAX), 2);
+    }
+
+    #[test]
+    fn wrapping_window() {
+        let mut window = WrappingWindow::<3>::new(8);
+        for i in 0u64..700 {
+            assert!(window.insert(i % 256), "{i}");
+        }
+        assert_eq!(window.window().highest_seen(), Some(699));
+        assert!(!window.insert(699 % 256));
+        assert!(!window.insert(600 % 256));
+        assert!(!window.insert(256));
+
+        let mut window = WrappingWindow::<3>::new(8);
+        for i in 0..250 {
+            assert!(window.insert(i));
+        }
+        for i in [250, 252, 255, 1, 3, 254, 0, 253, 2, 251] {
+            assert!(window.insert(i), "{i}");
+        }
+        assert!(window.window().iter().rev().take(10).eq((250..260).rev()));
+        for i in [250, 255, 0, 3] {
+            assert!(!window.insert(i), "{i}");
+        }
+
+        let mut window = WrappingWindow::<3>::new(64);
+        assert!(window.insert(u64::MAX - 5));
+        assert_eq!(window.unwrap(u64::MAX), Some(u64::MAX));
+        assert_eq!(window.unwrap(3), None);
+    }
+
+    #[test]
+    fn wrapping_window_start() {
+        // the first sequence number is taken as is, even in the upper half of the sequence space
+        let mut window = WrappingWindow::<3>::new(32);
+        assert_eq!(window.unwrap(3_000_000_000), Some(3_000_000_000));
+        for seq in 3_000_000_000..3_000_000_100 {
+            assert!(window.insert(seq), "{seq}");
+        }
+        assert!(!window.insert(3_000_000_050));
+        assert!(window.insert(2_999_999_990));
+
+        let mut window = WrappingWindow::<3>::with_base(8, 250);
+        assert_eq!(window.unwrap(5), Some(261));
+        assert_eq!(window.unwrap(245), Some(245));
+        for seq in (250..256).chain(0..10) {
+            assert!(window.insert(seq), "{seq}");
+        }
+        assert_eq!(window.window().highest_seen(), Some(265));
+    }
+
+    #[test]
+    fn wrapping_window_max_distance() {
+        let mut window = WrappingWindow::<3>::new(32);
+        assert!(window.insert(10));
+        // a jump of half the sequence space would evict everything
+        assert_eq!(window.unwrap(10 + (1 << 31)), None);
+        assert!(!window.insert(11 + (1 << 30) + 1));
+        assert!(window.insert(11 + (1 << 30)));
+
+        let mut window = WrappingWindow::<3>::new(16);
+        window.set_max_distance(100);
+        assert!(window.insert(65_500));
+        assert_eq!(window.unwrap(65), Some(65_601));
+        assert_eq!(window.unwrap(66), None);
+        assert_eq!(window.unwrap(65_401), Some(65_401));
+        assert_eq!(window.unwrap(65_400), None);
+    }
+
+    #[test]
+    fn wrapping_window_end() {
+        let mut window = WrappingWindow::<3>::new(64);
+        assert!(window.insert(u64::MAX));
+        assert!(!window.insert(u64::MAX));
+        assert!(window.insert(u64::MAX - 1));
+        assert_eq!(window.unwrap(0), None);
+    }
+
+    #[test]
+    fn compact_roundtrip() {
+        let mut window = Window::<16>::new();
+        assert_eq!(window.export_compact(), [0, 0]);
+        assert_eq!(Window::<16>::import_compact(&[0, 0]), Ok(window.clone()));
+
+        window.insert_sorted([0, 3, 9]);
+        let compact = window.export_compact();
+        assert_eq!(compact, [0, 10, 0b0000_1001, 0b10]);
+        assert!(compact.len() < 16 * std::mem::size_of::<usize>());
+        assert_eq!(Window::<16>::import_compact(&compact), Ok(window.clone()));
+
+        let mut window = Window::<3>::new();
+        window.insert_sorted((0..1000).step_by(3));
+        let imported = Window::<3>::import_compact(&window.export_compact()).unwrap();
+        assert_eq!(imported, window);
+
+        let too_long = Window::<16>::new().export_compact();
+        assert_eq!(Window::<3>::import_compact(&[0, 0, 1]), Err(WindowDecodeError::InvalidLength));
+        assert_eq!(Window::<3>::import_compact(&too_long[..1]), Err(WindowDecodeError::InvalidLength));
+        let mut window = Window::<16>::new();
+        window.insert(1000);
+        assert!(matches!(
+            Window::<3>::import_compact(&window.export_compact()),
+            Err(WindowDecodeError::WrongSize { expected: 3, .. })
+        ));
+
+        // first_index = u64::MAX, then one bit at offset 0 or 5
+        let mut src = vec![0xFF; 9];
+        src.extend([6, 0b0000_0001]);
+        assert_eq!(Window::<3>::import_compact(&src), Ok(Window::from_raw([1, 0, 0], u64::MAX)));
+        src[10] = 0b0010_0000;
+        assert_eq!(Window::<3>::import_compact(&src), Err(WindowDecodeError::PastEnd));
+    }
+
+    #[test]
+    fn expanding_with_random() {
+        let mut window = Window::<5>::new();
+        let mut r = rand::thread_rng();
+        let mut nums = Vec::from_iter(std::iter::repeat_with(|| r.gen_range(0..1_000_000)).take(100_000));
+        nums.sort_unstable();
+        nums.dedup();
 
         for chunk in nums.chunks(5000) {
             for n in chunk {
@@ -183,5 +1836,29 @@ mod tests {
             assert!(!window.can_insert(*n), "{window:?} {n}");
             assert!(!window.insert(*n), "{window:?} {n}");
         }
+        window.validate().unwrap();
+    }
+
+    #[test]
+    fn validate_near_max() {
+        let mut window = Window::<3>::new();
+        window.reset_to(u64::MAX - 100);
+        window.insert_range(u64::MAX - 10, u64::MAX);
+        assert_eq!(window.validate(), Ok(()));
+
+        let bits = usize::BITS as u64;
+        let window = Window::<3>::from_raw([usize::MAX, 0b11, 0], u64::MAX - bits - 1);
+        assert_eq!(window.validate(), Ok(()));
+        assert_eq!(window.highest_seen(), Some(u64::MAX));
+        assert_eq!(window.iter().count() as u64, bits + 2);
+    }
+
+    #[test]
+    fn validate_rejects_overflow() {
+        let bits = usize::BITS as u64;
+        let hostile = Window::<3>::from_raw([usize::MAX, 0b111, 0], u64::MAX - bits - 1);
+        assert_eq!(hostile.validate(), Err(WindowDecodeError::PastEnd));
+        assert_eq!(Window::<3>::from_raw([0, 0, 1], u64::MAX - 5).validate(), Err(WindowDecodeError::PastEnd));
+        assert_eq!(Window::<3>::from_raw([0; 3], u64::MAX).validate(), Ok(()));
     }
 }
@@ -0,0 +1,9 @@
+// the expected errors name parking_lot's types, so only check them against the default lock. The versions are pinned
+// in the dev-dependencies, but the errors also walk through std's types, so regenerate them with `TRYBUILD=overwrite`
+// after upgrading rustc
+#![cfg(not(any(feature = "spin", feature = "single-thread")))]
+
+#[test]
+fn compile_fail() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,9 @@
+use miniproto::shared_slots::SharedSlots;
+
+fn main() {
+    let slots = SharedSlots::<u32>::new(1);
+    let occupied = slots.reserve().unwrap().insert(1);
+    std::thread::scope(|s| {
+        s.spawn(move || *occupied);
+    });
+}
@@ -0,0 +1,11 @@
+use std::cell::Cell;
+
+use miniproto::shared_slots::SharedSlots;
+
+fn main() {
+    let slots = SharedSlots::<Cell<u32>>::new(1);
+    let occupied = slots.reserve().unwrap().insert(Cell::new(1));
+    std::thread::scope(|s| {
+        s.spawn(|| occupied.set(2));
+    });
+}
@@ -0,0 +1,10 @@
+use std::rc::Rc;
+
+use miniproto::shared_slots::SharedSlots;
+
+fn main() {
+    let slots = SharedSlots::<Rc<u32>>::new(1);
+    std::thread::scope(|s| {
+        s.spawn(|| slots.insert(Rc::new(1)));
+    });
+}